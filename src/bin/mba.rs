@@ -0,0 +1,182 @@
+//! Command line interface to the obfuscator.
+//!
+//! Reads an expression from the arguments or from stdin
+//! and writes the obfuscated function to stdout.
+
+use std::io::Read;
+use std::process::ExitCode;
+use mba_wasm::{ObfuscationConfig, ObfuscationError, Printer, SolutionChoice, Width, obfuscate};
+use mba_wasm::simplify::{Basis, simplify};
+
+const USAGE: &str = "\
+Usage: mba [OPTIONS] [EXPR]
+
+Obfuscates EXPR and prints it as a function.
+With --simplify, EXPR is simplified instead, if it is linear MBA.
+EXPR can also be a block of assignments with several results,
+e.g. 'a = x + y; b = a ^ z; return a * b, b'.
+If EXPR is missing or '-', the expression is read from stdin.
+Use '--' before EXPR if it starts with '-'.
+
+Options:
+  -w, --width <BITS>       Integer width from 1 to 128 [default: 8]
+  -p, --printer <PRINTER>  Output language: c, rust, llvm, python, js, smt, tex
+                           or default [default: c]
+  -a, --aux-vars <N>       Number of auxiliary variables [default: 0]
+  -d, --rewrite-depth <N>  Depth of the rewrite operations [default: 3]
+  -c, --rewrite-count <N>  Number of rewrite operations [default: 24]
+  -n, --perm-poly          Wrap the linear MBA in a permutation polynomial
+                           and its inverse to make it non-linear
+  -l, --solution <MODE>    How the coefficients are picked: random, short,
+                           sparse or particular [default: random]
+  -i, --signed             Use signed division, remainder and right shifts
+                           and print a function on signed integers
+  -s, --seed <N>           Seed for reproducible output [default: random]
+  -S, --simplify           Simplify EXPR instead of obfuscating it
+  -h, --help               Print this help";
+
+fn main() -> ExitCode {
+    let (cfg, simp) = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        },
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        },
+    };
+
+    let res = if simp {
+        simplify(&cfg.expr, cfg.width, Basis::Conjunction, cfg.printer, cfg.signed)
+    } else {
+        obfuscate(&cfg)
+    };
+
+    match res {
+        Ok(s) => {
+            println!("{}", s);
+            ExitCode::SUCCESS
+        },
+        Err(e) => {
+            eprintln!("error: {}", e);
+            if let ObfuscationError::Parse(e) = &e {
+                print_span(&cfg.expr, e.span.start, e.span.end);
+            }
+            ExitCode::FAILURE
+        },
+    }
+}
+
+/// Parses the command line arguments into a config
+/// and whether to simplify instead of obfuscate.
+/// Returns None if the help should be printed.
+fn parse_args(
+    mut args: impl Iterator<Item = String>
+) -> Result<Option<(ObfuscationConfig, bool)>, String> {
+    let mut cfg = ObfuscationConfig::new();
+    let mut simp = false;
+    let mut expr = None;
+    let mut options = true;
+
+    while let Some(arg) = args.next() {
+        // Everything after `--` is the expression.
+        if !options {
+            set_expr(&mut expr, arg)?;
+            continue;
+        } else if arg == "--" {
+            options = false;
+            continue;
+        }
+
+        // Support both `--width 8` and `--width=8`.
+        let (flag, inline) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with('-') => (f.to_owned(), Some(v.to_owned())),
+            _ => (arg.clone(), None),
+        };
+
+        let mut value = || inline.clone()
+            .or_else(|| args.next())
+            .ok_or_else(|| format!("Missing value for '{}'", flag));
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-w" | "--width" => cfg.width = parse_width(&value()?)?,
+            "-p" | "--printer" => cfg.printer = parse_printer(&value()?)?,
+            "-a" | "--aux-vars" => cfg.aux_vars = parse_num(&flag, &value()?)?,
+            "-d" | "--rewrite-depth" => cfg.rewrite_depth = parse_num(&flag, &value()?)?,
+            "-c" | "--rewrite-count" => cfg.rewrite_count = parse_num(&flag, &value()?)?,
+            "-n" | "--perm-poly" => cfg.perm_poly = true,
+            "-l" | "--solution" => cfg.solution = parse_solution(&value()?)?,
+            "-i" | "--signed" => cfg.signed = true,
+            "-s" | "--seed" => cfg.seed = Some(parse_num(&flag, &value()?)?),
+            "-S" | "--simplify" => simp = true,
+            f if f.starts_with('-') && f != "-" => {
+                return Err(format!("Unknown option '{}'", f));
+            },
+            _ => set_expr(&mut expr, arg)?,
+        }
+    }
+
+    cfg.expr = match expr {
+        Some(e) if e != "-" => e,
+        _ => {
+            let mut s = String::new();
+            std::io::stdin().read_to_string(&mut s)
+                .map_err(|e| format!("Failed to read stdin: {}", e))?;
+            s.trim().to_owned()
+        },
+    };
+
+    Ok(Some((cfg, simp)))
+}
+
+fn set_expr(expr: &mut Option<String>, arg: String) -> Result<(), String> {
+    if expr.is_some() {
+        return Err(format!("Unexpected argument '{}'", arg));
+    }
+    *expr = Some(arg);
+    Ok(())
+}
+
+fn parse_width(s: &str) -> Result<Width, String> {
+    let bits = s.parse().map_err(|_| format!("Invalid width '{}'", s))?;
+    Width::new(bits)
+}
+
+fn parse_printer(s: &str) -> Result<Printer, String> {
+    Ok(match s.to_ascii_lowercase().as_str() {
+        "default" => Printer::Default,
+        "c" => Printer::C,
+        "rust" => Printer::Rust,
+        "llvm" => Printer::LlvmIr,
+        "python" => Printer::Python,
+        "js" | "javascript" => Printer::JavaScript,
+        "smt" | "smtlib" => Printer::SmtLib,
+        "tex" => Printer::Tex,
+        _ => return Err(format!("Invalid printer '{}'", s)),
+    })
+}
+
+fn parse_solution(s: &str) -> Result<SolutionChoice, String> {
+    Ok(match s.to_ascii_lowercase().as_str() {
+        "particular" => SolutionChoice::Particular,
+        "random" => SolutionChoice::Random,
+        "short" => SolutionChoice::Short,
+        "sparse" => SolutionChoice::Sparse,
+        _ => return Err(format!("Invalid solution '{}'", s)),
+    })
+}
+
+fn parse_num<T: std::str::FromStr>(flag: &str, s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("Invalid value '{}' for '{}'", s, flag))
+}
+
+/// Underlines the byte range `start..end` of the input.
+fn print_span(input: &str, start: usize, end: usize) {
+    let pad = input[..start].chars().count();
+    let len = input[start..end].chars().count().max(1);
+    eprintln!("  {}", input);
+    eprintln!("  {}{}", " ".repeat(pad), "^".repeat(len));
+}
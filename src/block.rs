@@ -0,0 +1,259 @@
+//! Blocks of assignments with one or more results,
+//! e.g. `a = x + y; b = a ^ z; return a * b, b`.
+
+use std::rc::Rc;
+use std::fmt::Write;
+use std::collections::BTreeSet;
+use crate::expr::{Expr, sort_args};
+use crate::numbers::UniformNum;
+use crate::parser::{Lexer, Token, ParseError};
+use crate::printer::{Printer, c_type, c_compute_type, rust_int};
+use crate::uniform_expr::Valuation;
+
+/// The statements of a block, reduced to the returned expressions.
+///
+/// The assigned variables are substituted into the statements after them,
+/// so the results are expressions in the inputs
+/// that share the assigned subexpressions.
+#[derive(Debug)]
+pub struct Block<T> {
+    /// The returned expressions.
+    pub outputs: Vec<Rc<Expr<T>>>,
+}
+
+impl<T: UniformNum> Block<T> {
+    /// Parses a block.
+    /// The statements are separated by `;` and the results by `,`.
+    /// The `return` is optional, so a single expression is also a block.
+    pub fn from_string<U: AsRef<str>>(s: U) -> Result<Self, ParseError> {
+        let mut l = Lexer::new(s.as_ref());
+
+        // The assigned variables and their current values.
+        let mut locals: Vec<(String, Rc<Expr<T>>)> = Vec::new();
+
+        // The variables that were used as inputs.
+        let mut inputs = BTreeSet::new();
+
+        loop {
+            let ret = l.peek().0 == Token::Ident("return");
+            if ret {
+                l.next_token();
+            }
+
+            let start = l.peek().1;
+            let e = Expr::parse(&mut l, 0)?;
+
+            // Is this an assignment?
+            if let (Expr::Var(name), (Token::Sym("="), _), false) = (&e, l.peek(), ret) {
+                // Otherwise the substitution would change the earlier statements.
+                if inputs.contains(name) {
+                    return Err(ParseError::new(
+                        Token::Ident(name), start, &["variable that is not an input"]
+                    ));
+                }
+
+                l.next_token();
+                let v = Expr::parse(&mut l, 0)?;
+                l.expect(";", &["operator", "';'"])?;
+                let v = Self::substitute_locals(v, &mut locals, &mut inputs);
+                locals.retain(|(n, _)| n != name);
+                locals.push((name.clone(), v));
+                continue;
+            }
+
+            // Otherwise these are the results.
+            let mut outputs = vec![Self::substitute_locals(e, &mut locals, &mut inputs)];
+            while let (Token::Sym(","), _) = l.peek() {
+                l.next_token();
+                let e = Expr::parse(&mut l, 0)?;
+                outputs.push(Self::substitute_locals(e, &mut locals, &mut inputs));
+            }
+
+            if let (Token::Sym(";"), _) = l.peek() {
+                l.next_token();
+            }
+
+            l.expect_eof(&["operator", "','", "end of input"])?;
+
+            // The locals have to be dropped before the outputs are used,
+            // because the reference counts determine what is shared.
+            drop(locals);
+            return Ok(Self { outputs });
+        }
+    }
+
+    /// Replaces the assigned variables in the expression by their values
+    /// and remembers the other variables as inputs.
+    fn substitute_locals(
+        mut e: Expr<T>,
+        locals: &mut [(String, Rc<Expr<T>>)],
+        inputs: &mut BTreeSet<String>,
+    ) -> Rc<Expr<T>> {
+        let vars = e.vars();
+        if let Expr::Var(v) = &e {
+            if let Some((_, l)) = locals.iter().find(|(n, _)| n == v) {
+                return l.clone();
+            }
+        }
+
+        for (name, l) in locals.iter_mut() {
+            if vars.contains(name) {
+                e.substitute(l, name);
+            }
+        }
+
+        inputs.extend(vars.into_iter().filter(|v| locals.iter().all(|(n, _)| n != v)));
+        Rc::new(e)
+    }
+
+    /// Returns all variables in the outputs.
+    pub fn vars(&self) -> Vec<String> {
+        let v: BTreeSet<_> = self.outputs.iter()
+            .flat_map(|e| e.vars())
+            .collect();
+        v.into_iter().collect()
+    }
+
+    /// Evaluates the outputs, see [`Expr::eval`].
+    pub fn eval(&self, v: &Valuation<T>, signed: bool) -> Vec<T> {
+        self.outputs.iter().map(|e| e.eval(v, signed)).collect()
+    }
+
+    /// Prints the block as a function that returns all outputs.
+    /// With a single output, this is the same as [`Expr::print_as_fn`].
+    /// Otherwise the C function returns a struct, the Rust and Python
+    /// functions a tuple and the JavaScript function an array.
+    /// For SMT-LIB, there is a function for each output.
+    pub fn print_as_fn(&self, printer: Printer, signed: bool) -> String {
+        if let [e] = self.outputs.as_slice() {
+            return e.print_as_fn(printer, signed);
+        }
+
+        let mut input = self.vars();
+        sort_args(&mut input);
+
+        if printer == Printer::LlvmIr {
+            return self.print_llvm(&input, signed);
+        } else if printer == Printer::SmtLib {
+            let fns: Vec<_> = self.outputs.iter()
+                .enumerate()
+                .map(|(i, e)| e.print_smt_fn(&format!("f{}", i), &input, signed))
+                .collect();
+            return fns.join("\n");
+        }
+
+        // Stores a mapping of (sub)expressions to variables.
+        let mut vars = Vec::new();
+
+        // The results with the high bits cleared if necessary.
+        // The fields of the C struct truncate them.
+        let mask = printer.needs_mask(T::bits()) && printer != Printer::C;
+        let outputs: Vec<_> = self.outputs.iter().map(|e| {
+            let s = Expr::print_simple_rc(e, &mut vars, printer, signed);
+            if mask {
+                let pre = if Rc::strong_count(e) > 1 { 256 } else { e.precedence() };
+                Expr::<T>::mask(&s, pre, printer, signed)
+            } else {
+                s
+            }
+        }).collect();
+
+        let mut s = String::new();
+        if printer == Printer::Default {
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "{} = {}", var, init);
+            }
+            write!(&mut s, "({})", outputs.join(", "));
+        } else if printer == Printer::C {
+            let ty = c_compute_type(T::bits());
+            let arg_ty = c_type(T::bits(), signed);
+
+            s += "struct f_result {\n";
+            for i in 0..outputs.len() {
+                writeln!(&mut s, "\t{} r{};", arg_ty, i);
+            }
+            s += "};\n\n";
+
+            let args: Vec<_> = input.iter()
+                .map(|v| format!("{} {}", arg_ty, v))
+                .collect();
+            writeln!(&mut s, "struct f_result f({}) {{", args.join(", "));
+
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "\t{} {} = {};", ty, var, init);
+            }
+
+            write!(&mut s, "\treturn (struct f_result){{ {} }};\n}}", outputs.join(", "));
+        } else if printer == Printer::Rust {
+            let ty = format!("Wrapping<{}>", rust_int(T::bits(), signed));
+            let ret_ty = vec![ty.as_str(); outputs.len()].join(", ");
+
+            let args: Vec<_> = input.iter()
+                .map(|v| format!("{}: {}", v, ty))
+                .collect();
+            writeln!(&mut s, "fn f({}) -> ({}) {{", args.join(", "), ret_ty);
+
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "\tlet {} = {};", var, init);
+            }
+
+            write!(&mut s, "\t({})\n}}", outputs.join(", "));
+        } else if printer == Printer::Python {
+            writeln!(&mut s, "def f({}):", input.join(", "));
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "    {} = {}", var, init);
+            }
+            write!(&mut s, "    return {}", outputs.join(", "));
+        } else if printer == Printer::JavaScript {
+            writeln!(&mut s, "function f({}) {{", input.join(", "));
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "\tconst {} = {};", var, init);
+            }
+            write!(&mut s, "\treturn [{}];\n}}", outputs.join(", "));
+        } else if printer == Printer::Tex {
+            s += "\\begin{aligned}\n";
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "{} &= {} \\\\", var, init);
+            }
+            let fns: Vec<_> = outputs.iter()
+                .enumerate()
+                .map(|(i, o)| format!("f_{{{}}}({}) &= {}", i + 1, input.join(", "), o))
+                .collect();
+            write!(&mut s, "{}\n\\end{{aligned}}", fns.join(" \\\\\n"));
+        } else {
+            panic!("Unsupported printer.");
+        }
+
+        s
+    }
+
+    /// Prints the block as an LLVM IR function that returns a struct.
+    fn print_llvm(&self, input: &[String], signed: bool) -> String {
+        let ty = format!("i{}", T::bits());
+        let ret_ty = format!("{{ {} }}", vec![ty.as_str(); self.outputs.len()].join(", "));
+
+        let mut insts = Vec::new();
+        let mut vars = Vec::new();
+        let outputs: Vec<_> = self.outputs.iter()
+            .map(|e| Expr::print_llvm_rc(e, &ty, signed, &mut insts, &mut vars))
+            .collect();
+
+        // The aggregate continues the names of the instructions.
+        let mut agg = "undef".to_owned();
+        for (i, o) in outputs.iter().enumerate() {
+            let v = format!("%t.{}", insts.len());
+            insts.push(format!("{} = insertvalue {} {}, {} {}, {}", v, ret_ty, agg, ty, o, i));
+            agg = v;
+        }
+
+        let args: Vec<_> = input.iter()
+            .map(|v| format!("{} %{}", ty, v))
+            .collect();
+        let mut s = format!("define {} @f({}) {{\n", ret_ty, args.join(", "));
+        for i in &insts {
+            writeln!(&mut s, "\t{}", i);
+        }
+        write!(&mut s, "\tret {} {}\n}}", ret_ty, agg);
+        s
+    }
+}
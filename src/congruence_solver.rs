@@ -1,27 +1,38 @@
 use std::fmt::Display;
 use crate::matrix::Matrix;
 use crate::vector::Vector;
-use crate::numbers::UnsignedInt;
+use rand::Rng;
+use rand::distributions::{Standard, Distribution};
+use crate::numbers::{UnsignedInt, UniformNum, add_mod, neg_mod, mul_mod};
 
+#[derive(Clone)]
 pub struct AffineLattice<T> {
     pub offset: Vector<T>,
     pub basis: Vec<Vector<T>>,
+
+    /// The modulus of the entries,
+    /// where 0 stands for the number of values of `T`.
+    pub modulus: T,
 }
 
-impl<T> AffineLattice<T> {
+impl<T: UnsignedInt> AffineLattice<T> {
     pub fn empty() -> Self {
+        Self::empty_mod(T::zero())
+    }
+
+    /// The empty lattice mod n.
+    pub fn empty_mod(n: T) -> Self {
         Self {
             offset: Vector::empty(),
             basis: Vec::new(),
+            modulus: n,
         }
     }
 
     pub fn is_empty(&self) -> bool {
         self.offset.is_empty()
     }
-}
 
-impl<T: UnsignedInt> AffineLattice<T> {
     pub fn to_tex(&self) -> String {
         let mut s = self.offset.to_tex();
         for (i, b) in self.basis.iter().enumerate() {
@@ -39,6 +50,509 @@ impl<T: UnsignedInt> AffineLattice<T> {
     }
 }
 
+impl<T: UniformNum> AffineLattice<T> {
+    /// Brings the basis into Hermite normal form and reduces the offset
+    /// by it, so equal lattices have the same representation.
+    pub fn canonicalize(&mut self) {
+        if self.is_empty() {
+            return;
+        }
+
+        let n = self.modulus;
+        self.basis = hnf_rows(std::mem::take(&mut self.basis), self.offset.dim, n);
+        reduce(&self.basis, &mut self.offset, n);
+    }
+
+    /// Returns the canonical representation, see [`Self::canonicalize`].
+    pub fn canonical(mut self) -> Self {
+        self.canonicalize();
+        self
+    }
+
+    /// Is the vector in the lattice?
+    pub fn contains(&self, x: &Vector<T>) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        debug_assert!(x.dim == self.offset.dim, "Incompatible dimensions");
+        let n = self.modulus;
+        let basis = hnf_rows(self.basis.clone(), x.dim, n);
+        let mut v = x.clone();
+        add_mul(&mut v, &self.offset, neg_mod(mod_n(T::one(), n), n), n);
+        reduce(&basis, &mut v, n);
+        v.is_zero()
+    }
+
+    /// The intersection of the lattices.
+    pub fn intersection(&self, other: &Self) -> Self {
+        debug_assert!(self.modulus == other.modulus, "Incompatible moduli");
+        let n = self.modulus;
+        if self.is_empty() || other.is_empty() {
+            return Self::empty_mod(n);
+        }
+
+        debug_assert!(self.offset.dim == other.offset.dim, "Incompatible dimensions");
+
+        // A single point is either in the other lattice or not.
+        if other.basis.is_empty() {
+            return match self.contains(&other.offset) {
+                true => other.clone(),
+                false => Self::empty_mod(n),
+            };
+        } else if self.basis.is_empty() {
+            return other.intersection(self);
+        }
+
+        // Solve o_1 + Ua = o_2 + Vb, where the columns of U and V
+        // are the bases, for (a, b) and map the solutions to o_1 + Ua.
+        let dim = self.offset.dim;
+        let m = self.basis.len();
+        let mut a = Matrix::zero(dim, m + other.basis.len());
+        for (j, v) in self.basis.iter().enumerate() {
+            for i in 0..dim {
+                a[(i, j)] = v[i];
+            }
+        }
+        for (j, v) in other.basis.iter().enumerate() {
+            for i in 0..dim {
+                a[(i, m + j)] = neg_mod(v[i], n);
+            }
+        }
+
+        let mut b = other.offset.clone();
+        add_mul(&mut b, &self.offset, neg_mod(mod_n(T::one(), n), n), n);
+
+        let l = solve_congruences_mod(a, &b, n);
+        if l.is_empty() {
+            return Self::empty_mod(n);
+        }
+
+        // Maps the coefficients a to Ua.
+        let combine = |c: &Vector<T>| {
+            let mut v = Vector::zero(dim);
+            for (u, c) in self.basis.iter().zip(c.iter()) {
+                add_mul(&mut v, u, *c, n);
+            }
+            v
+        };
+
+        let mut offset = combine(&l.offset);
+        add_mul(&mut offset, &self.offset, mod_n(T::one(), n), n);
+
+        Self {
+            offset,
+            basis: l.basis.iter().map(combine).collect(),
+            modulus: n,
+        }.canonical()
+    }
+
+    /// Combines a lattice mod n_1 and one mod n_2 for coprime n_1 and n_2
+    /// into the lattice mod n_1 n_2 of the vectors that are in this lattice mod n_1
+    /// and in the other one mod n_2 by the chinese remainder theorem.
+    /// The product has to fit into `T`.
+    pub fn crt(&self, other: &Self) -> Self {
+        let (n1, n2) = (self.modulus, other.modulus);
+        let n = n1 * n2;
+        assert!(!n1.is_zero() && !n2.is_zero() && n / n2 == n1,
+            "The product of the moduli {} and {} doesn't fit.", n1, n2);
+        assert!(ext_gcd(n1, n2, n).0.is_one(), "The moduli {} and {} are not coprime.", n1, n2);
+        if self.is_empty() || other.is_empty() {
+            return Self::empty_mod(n);
+        }
+
+        debug_assert!(self.offset.dim == other.offset.dim, "Incompatible dimensions");
+
+        // The vectors mod n that are in the lattice mod m.
+        let lift = |l: &Self, m: T| {
+            let mut basis = l.basis.clone();
+            for i in 0..l.offset.dim {
+                let mut v = Vector::zero(l.offset.dim);
+                v[i] = m;
+                basis.push(v);
+            }
+            Self { offset: l.offset.clone(), basis, modulus: n }
+        };
+
+        lift(self, n1).intersection(&lift(other, n2))
+    }
+
+    /// The sum of the lattices, i.e. all sums of a vector
+    /// in this lattice and one in the other.
+    pub fn sum(&self, other: &Self) -> Self {
+        debug_assert!(self.modulus == other.modulus, "Incompatible moduli");
+        let n = self.modulus;
+        if self.is_empty() || other.is_empty() {
+            return Self::empty_mod(n);
+        }
+
+        debug_assert!(self.offset.dim == other.offset.dim, "Incompatible dimensions");
+
+        let mut offset = self.offset.clone();
+        add_mul(&mut offset, &other.offset, mod_n(T::one(), n), n);
+        Self {
+            offset,
+            basis: self.basis.iter().chain(&other.basis).cloned().collect(),
+            modulus: n,
+        }.canonical()
+    }
+
+    /// The base 2 logarithm of the number of vectors in the lattice
+    /// or None if it is empty or, for moduli that aren't powers of two,
+    /// the number isn't a power of two.
+    pub fn count_log2(&self) -> Option<u32> {
+        if self.is_empty() {
+            return None;
+        }
+
+        // Every vector is a unique combination of the basis in Hermite normal form,
+        // where the coefficient of a vector with the pivot d is less than n/d.
+        let basis = hnf_rows(self.basis.clone(), self.offset.dim, self.modulus);
+        basis.iter()
+            .map(|b| match self.modulus.is_zero() {
+                true => Some(T::bits() - exponent(b[pivot(b)])),
+                false => {
+                    let k = (self.modulus / b[pivot(b)]).to_u128();
+                    k.is_power_of_two().then(|| k.trailing_zeros())
+                },
+            })
+            .sum()
+    }
+
+    /// The number of vectors in the lattice
+    /// or None if it doesn't fit into a u128.
+    pub fn count(&self) -> Option<u128> {
+        if self.is_empty() {
+            return Some(0);
+        } else if self.modulus.is_zero() {
+            return 1u128.checked_shl(self.count_log2().unwrap());
+        }
+
+        let n = self.modulus;
+        hnf_rows(self.basis.clone(), self.offset.dim, n)
+            .iter()
+            .try_fold(1u128, |c, b| c.checked_mul((n / b[pivot(b)]).to_u128()))
+    }
+
+    /// Returns an iterator over the vectors in the lattice
+    /// that stops after at most `limit` vectors.
+    /// Every vector is returned once, so without the limit
+    /// there would be [`Self::count`] vectors.
+    pub fn points(&self, limit: usize) -> Points<T> {
+        let n = self.modulus;
+        let basis = match self.is_empty() {
+            true => Vec::new(),
+            false => hnf_rows(self.basis.clone(), self.offset.dim, n),
+        };
+        let bounds = basis.iter()
+            .map(|b| match n.is_zero() {
+                true => pow2(T::bits() - exponent(b[pivot(b)])),
+                false => n / b[pivot(b)],
+            })
+            .collect();
+
+        Points {
+            coeffs: vec![T::zero(); basis.len()],
+            basis,
+            bounds,
+            modulus: n,
+            next: (!self.is_empty()).then(|| self.offset.clone()),
+            limit,
+        }
+    }
+
+    /// Returns a uniformly random vector in the lattice.
+    ///
+    /// Mapping the coefficients to the combination of the basis vectors
+    /// is a surjective homomorphism of finite groups, so every vector is
+    /// the image of the same number of coefficients. Choosing them uniformly
+    /// thus gives every vector the same probability for any basis
+    /// and the basis doesn't have to be brought into Hermite normal form.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Vector<T>
+        where Standard: Distribution<T>
+    {
+        assert!(!self.is_empty(), "Can't sample from an empty lattice.");
+
+        let n = self.modulus;
+        let mut x = self.offset.clone();
+        for b in &self.basis {
+            let c = match n.is_zero() {
+                true => rng.gen::<T>(),
+                false => T::from_u128(rng.gen_range(0..n.to_u128())),
+            };
+            add_mul(&mut x, b, c, n);
+        }
+        x
+    }
+
+    /// Searches for a vector in the lattice with small entries,
+    /// interpreted as signed integers, or if `sparse` is set,
+    /// with few non-zero entries.
+    /// For moduli other than 2^N, the entries greater than n/2
+    /// are interpreted as negative.
+    ///
+    /// Finding the smallest one is hard in general, so this starts
+    /// with the offset and the canonical offset and adds multiples
+    /// of the basis vectors of both bases that shrink the entries
+    /// as long as that makes the vector smaller.
+    pub fn small_point(&self, sparse: bool) -> Vector<T> {
+        let n = self.modulus;
+        let l = self.clone().canonical();
+        let basis: Vec<_> = l.basis.iter().chain(&self.basis).collect();
+
+        // The sizes of the entries, as the number of non-zero entries
+        // and the sum of the absolute values, in the order they are compared.
+        let size = |x: &Vector<T>, idx: &[usize]| {
+            let (nz, abs) = idx.iter()
+                .map(|&i| abs_mod(x[i], n))
+                .fold((0, 0u128), |(nz, abs), e| {
+                    (nz + (e != 0) as u128, abs.saturating_add(e))
+                });
+            if sparse { (nz, abs) } else { (abs, nz) }
+        };
+
+        let all: Vec<_> = (0..l.offset.dim).collect();
+        [self.offset.clone(), l.offset]
+            .into_iter()
+            .map(|x| Self::shrink(x, &basis, n, |x, idx| size(x, idx)))
+            .min_by_key(|x| size(x, &all))
+            .unwrap()
+    }
+
+    /// Adds multiples of the basis vectors to x while that decreases the size.
+    fn shrink<F, S>(mut x: Vector<T>, basis: &[&Vector<T>], n: T, size: F) -> Vector<T>
+        where
+            F: Fn(&Vector<T>, &[usize]) -> S,
+            S: Ord,
+    {
+        let one = mod_n(T::one(), n);
+        for _ in 0..SMALL_POINT_ROUNDS {
+            let mut improved = false;
+            for &b in basis {
+                // Only the entries where b is non-zero change.
+                let support: Vec<_> = (0..b.dim)
+                    .filter(|&i| !b[i].is_zero())
+                    .collect();
+
+                // The multiples that reduce an entry mod the divisor of n
+                // in the basis vector to the smallest positive or negative value.
+                let mut candidates = vec![one, neg_mod(one, n)];
+                for &i in support.iter().take(SMALL_POINT_CANDIDATES) {
+                    let (d, u) = normalize(b[i], n);
+                    let c = mul_mod(neg_mod(x[i] / d, n), u, n);
+                    candidates.push(c);
+                    candidates.push(add_mod(c, neg_mod(u, n), n));
+                }
+
+                let mut best = size(&x, &support);
+                let mut best_c = None;
+                for c in candidates {
+                    let mut y = x.clone();
+                    add_mul(&mut y, b, c, n);
+                    let s = size(&y, &support);
+                    if s < best {
+                        best = s;
+                        best_c = Some(c);
+                    }
+                }
+
+                if let Some(c) = best_c {
+                    add_mul(&mut x, b, c, n);
+                    improved = true;
+                }
+            }
+
+            if !improved {
+                break;
+            }
+        }
+
+        x
+    }
+}
+
+/// The maximum number of times all basis vectors are tried in [`AffineLattice::small_point`].
+const SMALL_POINT_ROUNDS: usize = 32;
+
+/// The maximum number of entries per basis vector that are
+/// tried to be reduced in [`AffineLattice::small_point`].
+const SMALL_POINT_CANDIDATES: usize = 16;
+
+/// An iterator over the vectors in a lattice, see [`AffineLattice::points`].
+pub struct Points<T> {
+    /// The basis in Hermite normal form.
+    basis: Vec<Vector<T>>,
+
+    /// The number of values of each coefficient,
+    /// where 0 stands for the number of values of `T`.
+    bounds: Vec<T>,
+
+    /// The modulus of the lattice.
+    modulus: T,
+
+    /// The coefficients of the next vector.
+    coeffs: Vec<T>,
+
+    /// The next vector or None if all were returned.
+    next: Option<Vector<T>>,
+
+    /// The number of vectors that can still be returned.
+    limit: usize,
+}
+
+impl<T: UniformNum> Iterator for Points<T> {
+    type Item = Vector<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.limit == 0 {
+            return None;
+        }
+
+        let x = self.next.take()?;
+        self.limit -= 1;
+
+        // Increment the coefficients like the digits of a number.
+        let n = self.modulus;
+        let mut y = x.clone();
+        for ((b, c), bound) in self.basis.iter().zip(&mut self.coeffs).zip(&self.bounds) {
+            *c += T::one();
+            add_mul(&mut y, b, mod_n(T::one(), n), n);
+            if *c != *bound {
+                self.next = Some(y);
+                return Some(x);
+            }
+
+            // The coefficient wraps around to zero.
+            *c = T::zero();
+            add_mul(&mut y, b, neg_mod(mod_n(*bound, n), n), n);
+        }
+
+        // All coefficients wrapped around, so this was the last vector.
+        Some(x)
+    }
+}
+
+/// Lattices are equal if they contain the same vectors.
+impl<T: UniformNum> PartialEq for AffineLattice<T> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.modulus != other.modulus {
+            return false;
+        }
+
+        let l = self.clone().canonical();
+        let r = other.clone().canonical();
+        l.offset == r.offset && l.basis == r.basis
+    }
+}
+
+/// Computes the Hermite normal form of the lattice
+/// spanned by the rows of A over the integers mod 2^N.
+///
+/// The rows of the result are in echelon form, the pivots are powers of two
+/// and the entries above a pivot are less than it.
+/// Because 2^(N-k) times a row with the pivot 2^k is zero in the pivot column,
+/// these multiples are also reduced, so every vector in the lattice whose
+/// first non-zero entry is in column c is a combination of the rows
+/// whose pivots are in columns c and after.
+/// This makes the result unique (it is called the Howell form).
+pub fn hermite_normal_form<T: UniformNum>(a: &Matrix<T>) -> Matrix<T> {
+    let rows = (0..a.rows)
+        .map(|i| Vector::from_slice(a.row(i)))
+        .collect();
+    let rows = hnf_rows(rows, a.cols, T::zero());
+
+    let mut h = Matrix::zero(rows.len(), a.cols);
+    for (i, r) in rows.iter().enumerate() {
+        h.row_mut(i).copy_from_slice(r.entries());
+    }
+    h
+}
+
+/// Computes the Hermite normal form of the vectors mod n,
+/// see [`hermite_normal_form`].
+/// For moduli other than 2^N, the pivots are the divisors of n
+/// instead of powers of two.
+fn hnf_rows<T: UniformNum>(mut rows: Vec<Vector<T>>, dim: usize, n: T) -> Vec<Vector<T>> {
+    let mut h: Vec<Vector<T>> = Vec::new();
+
+    for c in 0..dim {
+        // Find the row with the smallest divisor of n in the column,
+        // which divides all others for powers of two.
+        let pivot = rows.iter()
+            .enumerate()
+            .filter(|(_, r)| !r[c].is_zero())
+            .min_by_key(|(_, r)| normalize(r[c], n).0)
+            .map(|(i, _)| i);
+
+        let mut p = match pivot {
+            Some(i) => rows.swap_remove(i),
+            None => continue,
+        };
+
+        // Multiply by a unit, so the pivot is the divisor.
+        let (mut d, u) = normalize(p[c], n);
+        p = scale(&p, u, n);
+
+        // Eliminate the column in the other rows.
+        for r in &mut rows {
+            if r[c].is_zero() {
+                continue;
+            }
+
+            if (r[c] % d).is_zero() {
+                let m = neg_mod(r[c] / d, n);
+                add_mul(r, &p, m, n);
+                continue;
+            }
+
+            // Otherwise replace both rows by combinations, such that the pivot
+            // is the gcd of the entries, which divides n, since d does,
+            // and the entry in the other row is zero.
+            let e = r[c];
+            let (g, s, t) = ext_gcd(d, e, n);
+            let mut q = scale(&p, s, n);
+            add_mul(&mut q, r, t, n);
+            *r = scale(r, d / g, n);
+            add_mul(r, &p, neg_mod(e / g, n), n);
+            p = q;
+            d = g;
+        }
+
+        // The multiple of the pivot row that is zero in the column.
+        let k = match n.is_zero() {
+            true => pow2(T::bits() - exponent(d)),
+            false => n / d,
+        };
+        if !mod_n(k, n).is_zero() {
+            rows.push(scale(&p, k, n));
+        }
+
+        h.push(p);
+    }
+
+    // Reduce the entries above the pivots.
+    for i in 0..h.len() {
+        let (above, below) = h.split_at_mut(i);
+        let pivot = std::slice::from_ref(&below[0]);
+        for r in above {
+            reduce(pivot, r, n);
+        }
+    }
+
+    h
+}
+
+/// Reduces the vector by the rows of a Hermite normal form mod n.
+/// The result is zero iff the vector is in the lattice.
+fn reduce<T: UniformNum>(h: &[Vector<T>], v: &mut Vector<T>, n: T) {
+    for r in h {
+        let c = pivot(r);
+        let m = neg_mod(v[c] / r[c], n);
+        if !m.is_zero() {
+            add_mul(v, r, m, n);
+        }
+    }
+}
 
 /// Solves ax=b mod n where n = 2^8 for u8.
 /// Returns None if there is no solution.
@@ -93,22 +607,47 @@ pub fn solve_scalar_congruence<T: UnsignedInt>(
 
 /// Solves a system of linear congruences Ax=b.
 pub fn solve_congruences<T: UnsignedInt>(
-    mut a: Matrix<T>, b: &Vector<T>
+    a: Matrix<T>, b: &Vector<T>
+) -> AffineLattice<T> {
+    solve_congruences_mod(a, b, T::zero())
+}
+
+/// Solves a system of linear congruences Ax=b mod n for any modulus n.
+/// The modulus `n = 0` stands for the number of values of `T`,
+/// e.g. 2^8 for u8, which is what [`solve_congruences`] uses.
+/// The entries of A and b are reduced mod n.
+///
+/// The diagonalization works for odd and composite moduli
+/// just like for powers of two. Systems mod coprime moduli,
+/// e.g. the prime power factors of n, can also be solved separately
+/// and combined by the chinese remainder theorem with [`AffineLattice::crt`].
+///
+/// The lattice records the modulus and all its operations work mod n,
+/// except that [`AffineLattice::count_log2`] is None
+/// if the number of solutions isn't a power of two.
+pub fn solve_congruences_mod<T: UnsignedInt>(
+    mut a: Matrix<T>, b: &Vector<T>, n: T
 ) -> AffineLattice<T> {
     debug_assert!(a.rows == b.dim, "Invalid system of congruences");
 
+    let mut b = b.clone();
+    if !n.is_zero() {
+        a = a.map(|e| *e % n);
+        b = b.map(|e| *e % n);
+    }
+
     // Diagonalize the system.
-    let (s, t) = diagonalize(&mut a);
+    let (s, t) = diagonalize_mod(&mut a, n);
     
     // Transform the vector b.
     // We could already do this in diagonalize if we really wanted.
-    let b = (&s * b);
+    let b = s.mul_vec_mod(&b, n);
 
     // If there is a non-zero entry in b at index >a.min_dim()
     // then the system has no solution, since the corresponding
     // row in a is zero, so we are solving 0=x.
     if b.iter().skip(a.min_dim()).any(|e| *e != T::zero()) {
-        return AffineLattice::empty();
+        return AffineLattice::empty_mod(n);
     }
 
     // Some solution to the system.
@@ -119,10 +658,10 @@ pub fn solve_congruences<T: UnsignedInt>(
 
     // Solve the scalar linear congruences.
     for i in 0..a.min_dim() {
-        let (x, kern) = match solve_scalar_congruence(a[(i, i)], b[i]) {
+        let (x, kern) = match solve_scalar_congruence_mod(a[(i, i)], b[i], n) {
             // If there is no solution,
             // then the whole system does not have a solution.
-            None => return AffineLattice::empty(),
+            None => return AffineLattice::empty_mod(n),
             Some(s) => s,
         };
 
@@ -147,14 +686,15 @@ pub fn solve_congruences<T: UnsignedInt>(
         basis.push(v);
     }
 
-    offset = (&t * &offset);
+    offset = t.mul_vec_mod(&offset, n);
     for v in &mut basis {
-        *v = (&t * &*v);
+        *v = t.mul_vec_mod(v, n);
     }
 
     AffineLattice {
         offset,
-        basis
+        basis,
+        modulus: n,
     }
 }
 
@@ -162,6 +702,20 @@ pub fn solve_congruences<T: UnsignedInt>(
 /// and returns matrices (S, T), such that D=SAT.
 pub fn diagonalize<T: UnsignedInt>(
     a: &mut Matrix<T>
+) -> (Matrix<T>, Matrix<T>) {
+    diagonalize_mod(a, T::zero())
+}
+
+/// Computes a diagonal matrix D in-place
+/// and returns matrices (S, T), such that D=SAT mod n.
+/// The entries of A have to be less than n,
+/// where `n = 0` stands for the number of values of `T`.
+///
+/// The elimination only ever subtracts multiples of the pivot
+/// that are at most the entry, like the euclidean algorithm,
+/// so it works for any modulus.
+pub fn diagonalize_mod<T: UnsignedInt>(
+    a: &mut Matrix<T>, n: T
 ) -> (Matrix<T>, Matrix<T>) {
     // The matrices S and T are initialized to the identity.
     // S/T keeps track of the row/column operations.
@@ -199,9 +753,9 @@ pub fn diagonalize<T: UnsignedInt>(
                 // Try to eliminate every other entry in the column.
                 for k in i+1..a.rows {
                     if a[(k, i)] != T::zero() {
-                        let m = T::zero() - (a[(k, i)] / a[(i, i)]);
-                        a.row_multiply_add(i, k, m);
-                        s.row_multiply_add(i, k, m);
+                        let m = neg_mod(a[(k, i)] / a[(i, i)], n);
+                        a.row_multiply_add_mod(i, k, m, n);
+                        s.row_multiply_add_mod(i, k, m, n);
                     }
                 }
 
@@ -243,52 +797,716 @@ pub fn diagonalize<T: UnsignedInt>(
             // Try to eliminate every other entry in the row.
             for k in i+1..a.cols {
                 if a[(i, k)] != T::zero() {
-                    let m = T::zero() - (a[(i, k)] / a[(i, i)]);
-                    a.col_multiply_add(i, k, m);
-                    t.col_multiply_add(i, k, m);
+                    let m = neg_mod(a[(i, k)] / a[(i, i)], n);
+                    a.col_multiply_add_mod(i, k, m, n);
+                    t.col_multiply_add_mod(i, k, m, n);
                 }
             }
         }
     }
 
-    return (s, t);
+    (s, t)
+}
+
+/// The Smith normal form D=SAT of a matrix A over the integers mod 2^N,
+/// see [`smith_normal_form`].
+pub struct SmithNormalForm<T> {
+    /// The invertible row transformation.
+    pub s: Matrix<T>,
+
+    /// The diagonal matrix.
+    pub d: Matrix<T>,
+
+    /// The invertible column transformation.
+    pub t: Matrix<T>,
 }
 
-/// Solves ax=b mod n.
+impl<T: UniformNum> SmithNormalForm<T> {
+    /// The invariant factors d_1 | d_2 | ... on the diagonal of D.
+    /// They are powers of two followed by zeros.
+    pub fn invariant_factors(&self) -> Vec<T> {
+        (0..self.d.min_dim()).map(|i| self.d[(i, i)]).collect()
+    }
+
+    /// The exponents of the invariant factors, i.e. d_i = 2^k_i.
+    /// The exponent of a zero is N.
+    pub fn invariant_exponents(&self) -> Vec<u32> {
+        self.invariant_factors()
+            .into_iter()
+            .map(|d| exponent(d))
+            .collect()
+    }
+
+    /// The number of non-zero invariant factors.
+    pub fn rank(&self) -> usize {
+        self.invariant_factors()
+            .iter()
+            .take_while(|d| !d.is_zero())
+            .count()
+    }
+
+    /// The determinantal divisors d_1 d_2 ... d_k, i.e. the gcds
+    /// of the k-by-k minors of A, up to units.
+    /// For square matrices, the last one is the determinant up to a unit.
+    pub fn determinantal_divisors(&self) -> Vec<T> {
+        let mut k = 0;
+        self.invariant_exponents()
+            .into_iter()
+            .map(|e| {
+                k = (k + e).min(T::bits());
+                pow2(k)
+            })
+            .collect()
+    }
+
+    /// Returns the base 2 logarithm of the number of solutions of Ax=b
+    /// or None if there is no solution.
+    pub fn solution_count_log2(&self, b: &Vector<T>) -> Option<u32> {
+        debug_assert!(self.s.cols == b.dim, "Invalid system of congruences");
+
+        // The system is equivalent to Dx'=Sb with x=Tx'.
+        let b = &self.s * b;
+        if b.iter().skip(self.d.min_dim()).any(|e| !e.is_zero()) {
+            return None;
+        }
+
+        // d_i x'_i = b_i has 2^k_i solutions if 2^k_i divides b_i.
+        let mut count = 0;
+        for (i, k) in self.invariant_exponents().into_iter().enumerate() {
+            if exponent(b[i]) < k {
+                return None;
+            }
+            count += k;
+        }
+
+        // The variables that don't appear in any equation are arbitrary.
+        Some(count + (self.d.cols - self.d.min_dim()) as u32 * T::bits())
+    }
+}
+
+/// Computes the Smith normal form D=SAT of A over the integers mod 2^N.
+///
+/// Every non-zero number is a power of two times an odd number,
+/// which is a unit, so the pivot with the fewest trailing zeros
+/// divides all other entries and can be scaled to a power of two.
+/// This results in the unique diagonal with d_1 | d_2 | ...
+pub fn smith_normal_form<T: UniformNum>(a: &Matrix<T>) -> SmithNormalForm<T> {
+    let mut d = a.clone();
+    let mut s = Matrix::<T>::id(a.rows);
+    let mut t = Matrix::<T>::id(a.cols);
+
+    for i in 0..d.min_dim() {
+        // Find the entry with the fewest trailing zeros
+        // in the remaining submatrix.
+        let pivot = (i..d.rows)
+            .flat_map(|r| (i..d.cols).map(move |c| (r, c)))
+            .filter(|&(r, c)| !d[(r, c)].is_zero())
+            .min_by_key(|&(r, c)| exponent(d[(r, c)]));
+
+        // If the submatrix is zero, then we are done.
+        let (r, c) = match pivot {
+            Some(p) => p,
+            None => break,
+        };
+
+        // Move the pivot to the diagonal.
+        d.swap_rows(i, r);
+        s.swap_rows(i, r);
+        d.swap_columns(i, c);
+        t.swap_columns(i, c);
+
+        // Divide the row by the odd part of the pivot,
+        // so the pivot is a power of two.
+        let k = exponent(d[(i, i)]);
+        let odd = d[(i, i)] >> k as usize;
+        let inv = solve_scalar_congruence(odd, T::one()).unwrap().0;
+        for e in d.row_mut(i).iter_mut().chain(s.row_mut(i)) {
+            *e *= inv;
+        }
+
+        // The pivot divides every other entry,
+        // so the row and column can be eliminated exactly.
+        for j in i+1..d.rows {
+            let m = T::zero() - (d[(j, i)] >> k as usize);
+            d.row_multiply_add(i, j, m);
+            s.row_multiply_add(i, j, m);
+        }
+
+        for j in i+1..d.cols {
+            let m = T::zero() - (d[(i, j)] >> k as usize);
+            d.col_multiply_add(i, j, m);
+            t.col_multiply_add(i, j, m);
+        }
+    }
+
+    SmithNormalForm { s, d, t }
+}
+
+/// The index of the first non-zero entry of a row of a Hermite normal form.
+fn pivot<T: UniformNum>(r: &Vector<T>) -> usize {
+    r.iter().position(|e| !e.is_zero()).unwrap()
+}
+
+/// The number of trailing zeros, which is N for zero.
+fn exponent<T: UniformNum>(a: T) -> u32 {
+    a.to_u128().trailing_zeros().min(T::bits())
+}
+
+/// Returns 2^k, which is zero for k = N.
+fn pow2<T: UniformNum>(k: u32) -> T {
+    T::from_u128(1u128.checked_shl(k).unwrap_or(0))
+}
+
+/// Reduces a mod n, see [`add_mod`].
+fn mod_n<T: UnsignedInt>(a: T, n: T) -> T {
+    if n.is_zero() { a } else { a % n }
+}
+
+/// The absolute value of the representative of a mod n
+/// closest to zero, which for n = 0 is the value as a signed integer.
+fn abs_mod<T: UniformNum>(a: T, n: T) -> u128 {
+    if n.is_zero() {
+        a.to_i128().unsigned_abs()
+    } else {
+        std::cmp::min(a, n - a).to_u128()
+    }
+}
+
+/// Adds c times b to x mod n.
+fn add_mul<T: UnsignedInt>(x: &mut Vector<T>, b: &Vector<T>, c: T, n: T) {
+    for (e, b) in x.iter_mut().zip(b.iter()) {
+        *e = add_mod(*e, mul_mod(*b, c, n), n);
+    }
+}
+
+/// Returns c times v mod n.
+fn scale<T: UnsignedInt>(v: &Vector<T>, c: T, n: T) -> Vector<T> {
+    v.map(|e| mul_mod(*e, c, n))
+}
+
+/// Returns (d, u) for a non-zero a < n, where d = gcd(a, n)
+/// and u is a unit mod n with ua = d.
+fn normalize<T: UniformNum>(a: T, n: T) -> (T, T) {
+    if n.is_zero() {
+        let k = exponent(a);
+        let u = solve_scalar_congruence(a >> k as usize, T::one()).unwrap().0;
+        return (pow2(k), u);
+    }
+
+    // The solutions of ax = d are x + j(n/d) and one of them is a unit.
+    let (g, x, _) = ext_gcd(a, n, n);
+    let k = n / g;
+    let mut u = x;
+    while ext_gcd(u, n, n).0 != T::one() {
+        u = add_mod(u, k, n);
+    }
+    (g, u)
+}
+
+/// The extended euclidean algorithm for n != 0.
+/// Returns (g, s, t) where g = gcd(a, b) and sa + tb = g mod n.
+fn ext_gcd<T: UnsignedInt>(a: T, b: T, n: T) -> (T, T, T) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_s, mut s) = (T::one() % n, T::zero());
+    let (mut old_t, mut t) = (T::zero(), T::one() % n);
+    while !r.is_zero() {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        let qn = q % n;
+        (old_s, s) = (s, add_mod(old_s, neg_mod(mul_mod(qn, s, n), n), n));
+        (old_t, t) = (t, add_mod(old_t, neg_mod(mul_mod(qn, t, n), n), n));
+    }
+    (old_r, old_s, old_t)
+}
+
+/// Solves ax=b mod n for a, b < n.
+/// The modulus `n = 0` stands for the number of values of `T`.
 /// Returns None if there is no solution.
 /// Otherwise returns all solutions in the form (c, d)
 /// where c+di are all solutions.
 pub fn solve_scalar_congruence_mod<T: UnsignedInt>(
     a: T, b: T, n: T
 ) -> Option<(T, T)> {
-    assert!(!n.is_zero());
+    if n.is_zero() {
+        return solve_scalar_congruence(a, b);
+    }
+
     // Handle the case that a is zero, so we don't have to think about it.
     if a == T::zero() {
         return (b == T::zero()).then_some((T::zero(), T::one()));
     }
 
+    // The extended euclidean algorithm on a and n,
+    // but the Bezout coefficients are computed mod n.
     let (mut old_r, mut r) = (a, n);
-    let (mut old_t, mut t) = (T::zero(), T::one());
+    let (mut old_t, mut t) = (T::one(), T::zero());
     while !r.is_zero() {
         let q = old_r / r;
         (old_r, r) = (r, old_r - q * r);
-        (old_t, t) = (t, old_t - q * t);
+        (old_t, t) = (t, add_mod(old_t, neg_mod(mul_mod(q % n, t, n), n), n));
     }
 
     // old_r is gcd(a, n).
     let gcd = old_r;
 
-    // There is a solution iff gcd divides b, but we can also just check ax=b.
+    // There is a solution iff gcd divides b.
     // old_t is the Bezout coefficient: a*old_t=gcd(a, n) mod n.
-    let x = b / gcd * old_t;
-    if a * x != b {
+    if !(b % gcd).is_zero() {
         return None;
     }
+    let x = mul_mod(b / gcd, old_t, n);
+    debug_assert!(mul_mod(a, x, n) == b);
 
-    // The kernel is n / gcd which happens to be in t.
-    // If the kernel is greater than n/2 we can take -t
-    // which is smaller.
-    let kern = std::cmp::min(t, T::zero() - t);
+    // The kernel is generated by n / gcd, which is 0 if a is invertible.
+    Some((x, n / gcd % n))
+}
 
-    Some((x, t))
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use std::num::Wrapping;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use crate::numbers::UInt;
+    use super::*;
+
+    type W = Wrapping<u8>;
+
+    /// A random matrix whose entries have random numbers of trailing zeros,
+    /// so the invariant factors are not all one.
+    fn rand_matrix<T: UniformNum, R: Rng>(rows: usize, cols: usize, rng: &mut R) -> Matrix<T>
+        where Standard: Distribution<T>
+    {
+        let mut a = Matrix::zero(rows, cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                a[(i, j)] = rng.gen::<T>() << rng.gen_range(0..T::bits() as usize);
+            }
+        }
+        a
+    }
+
+    /// All vectors of the dimension.
+    fn all_vectors<T: UniformNum>(dim: usize) -> impl Iterator<Item = Vector<T>> {
+        let n = 1u128 << T::bits();
+        (0..n.pow(dim as u32)).map(move |mut i| {
+            let v: Vec<_> = (0..dim)
+                .map(|_| {
+                    let e = T::from_u128(i % n);
+                    i /= n;
+                    e
+                })
+                .collect();
+            Vector::<T>::from_slice(&v)
+        })
+    }
+
+    /// All solutions of Ax=b, found by trying every vector.
+    fn brute_force<T: UniformNum>(a: &Matrix<T>, b: &Vector<T>) -> Vec<Vector<T>> {
+        all_vectors(a.cols).filter(|x| &(a * x) == b).collect()
+    }
+
+    /// A random system that has a solution half of the time.
+    fn rand_system<T: UniformNum, R: Rng>(
+        rows: usize, cols: usize, rng: &mut R
+    ) -> (Matrix<T>, Vector<T>)
+        where Standard: Distribution<T>
+    {
+        let a = rand_matrix(rows, cols, rng);
+        let b = match rng.gen::<bool>() {
+            true => &a * &Vector::from_slice(&(0..cols).map(|_| rng.gen()).collect::<Vec<T>>()),
+            false => Vector::from_slice(&(0..rows).map(|_| rng.gen()).collect::<Vec<T>>()),
+        };
+        (a, b)
+    }
+
+    /// Checks D=SAT, that D is diagonal and the divisibility chain.
+    fn check_snf<T: UniformNum>(a: &Matrix<T>) -> SmithNormalForm<T> {
+        let snf = smith_normal_form(a);
+        assert!(&(&snf.s * a) * &snf.t == snf.d, "D != SAT");
+        for i in 0..snf.d.rows {
+            for j in 0..snf.d.cols {
+                assert!(i == j || snf.d[(i, j)].is_zero(), "D is not diagonal");
+            }
+        }
+
+        let f = snf.invariant_factors();
+        for &d in &f {
+            assert!(d == pow2(exponent(d)), "{} is not a power of two", d);
+        }
+        for w in f.windows(2) {
+            assert!(exponent(w[0]) <= exponent(w[1]), "{} doesn't divide {}", w[0], w[1]);
+        }
+        assert_eq!(snf.rank(), f.iter().filter(|d| !d.is_zero()).count());
+        snf
+    }
+
+    /// Checks the Smith normal form of random systems
+    /// and compares the number of solutions with brute force.
+    fn check_snf_counts<T: UniformNum>(max_cols: usize)
+        where Standard: Distribution<T>
+    {
+        let mut rng = ChaCha8Rng::seed_from_u64(T::bits() as u64);
+        for _ in 0..64 {
+            let rows = rng.gen_range(1..=3);
+            let cols = rng.gen_range(1..=max_cols);
+            let (a, b) = rand_system::<T, _>(rows, cols, &mut rng);
+            let snf = check_snf(&a);
+
+            // Equivalent systems have the same invariant factors.
+            let mut e = a.clone();
+            for _ in 0..8 {
+                let (i, j) = (rng.gen_range(0..rows), rng.gen_range(0..rows));
+                if i != j {
+                    e.row_multiply_add(i, j, rng.gen());
+                }
+                let (i, j) = (rng.gen_range(0..cols), rng.gen_range(0..cols));
+                if i != j {
+                    e.col_multiply_add(i, j, rng.gen());
+                }
+                e.swap_rows(i % rows, j % rows);
+            }
+            assert!(check_snf(&e).invariant_factors() == snf.invariant_factors());
+
+            let count = brute_force(&a, &b).len() as u128;
+            let expected = snf.solution_count_log2(&b).map_or(0, |k| 1u128 << k);
+            assert_eq!(count, expected);
+        }
+    }
+
+    /// Checks that different bases of the same lattice have the same Hermite normal form
+    /// and that membership and intersections of solution sets agree with brute force.
+    fn check_lattices<T: UniformNum>(dim: usize, iters: usize)
+        where Standard: Distribution<T>
+    {
+        let mut rng = ChaCha8Rng::seed_from_u64(T::bits() as u64);
+        for _ in 0..iters {
+            let k = rng.gen_range(1..=3);
+            let a = rand_matrix::<T, _>(k, dim, &mut rng);
+            let h = hermite_normal_form(&a);
+            for i in 0..h.rows {
+                let p = pivot(&Vector::<T>::from_slice(h.row(i)));
+                assert!(h[(i, p)] == pow2(exponent(h[(i, p)])), "The pivot is not a power of two");
+            }
+
+            // Another basis of the same lattice with a redundant vector.
+            let mut e = Matrix::zero(k + 1, dim);
+            for i in 0..k {
+                e.row_mut(i).copy_from_slice(a.row(i));
+            }
+            for i in 0..k {
+                e.row_multiply_add(i, k, rng.gen());
+            }
+            for _ in 0..8 {
+                let (i, j) = (rng.gen_range(0..=k), rng.gen_range(0..=k));
+                if i != j {
+                    e.row_multiply_add(i, j, rng.gen());
+                }
+                let odd = rng.gen::<T>() | T::one();
+                for x in e.row_mut(i) {
+                    *x *= odd;
+                }
+                e.swap_rows(i, j);
+            }
+            assert!(hermite_normal_form(&e) == h, "Different Hermite normal forms");
+
+            let rows = rng.gen_range(1..=3);
+            let (a1, b1) = rand_system::<T, _>(rows, dim, &mut rng);
+            let (a2, b2) = rand_system::<T, _>(rows, dim, &mut rng);
+            let l1 = solve_congruences(a1.clone(), &b1);
+            let l2 = solve_congruences(a2.clone(), &b2);
+            let l = l1.intersection(&l2);
+            for x in all_vectors(dim) {
+                let in1 = &a1 * &x == b1;
+                let in2 = &a2 * &x == b2;
+                assert_eq!(l1.contains(&x), in1);
+                assert_eq!(l.contains(&x), in1 && in2);
+            }
+
+            // An equivalent system has the same solutions.
+            let (mut a3, mut b3) = (a1.clone(), b1.clone());
+            if rows > 1 {
+                let m = rng.gen();
+                a3.row_multiply_add(0, 1, m);
+                let s = b3[0] * m;
+                b3[1] += s;
+            }
+            assert!(solve_congruences(a3, &b3) == l1, "Different lattices for equivalent systems");
+        }
+    }
+
+    /// Checks that the points of solution lattices are exactly the solutions
+    /// and that their number is the count and samples are solutions.
+    fn check_points<T: UniformNum>(dim: usize)
+        where Standard: Distribution<T>
+    {
+        let mut rng = ChaCha8Rng::seed_from_u64(T::bits() as u64);
+        for _ in 0..32 {
+            let rows = rng.gen_range(1..=3);
+            let (a, b) = rand_system::<T, _>(rows, dim, &mut rng);
+            let l = solve_congruences(a.clone(), &b);
+            let solutions = brute_force(&a, &b);
+            assert_eq!(l.count(), Some(solutions.len() as u128));
+            assert_eq!(l.points(usize::MAX).count() as u128, l.count().unwrap());
+
+            let mut points: Vec<_> = l.points(usize::MAX)
+                .map(|x| {
+                    assert!(&a * &x == b, "The point is not a solution");
+                    x.iter().map(|e| e.to_u128()).collect::<Vec<_>>()
+                })
+                .collect();
+            points.sort();
+            points.dedup();
+            assert_eq!(points.len(), solutions.len(), "The points are not distinct");
+
+            let limit = rng.gen_range(0..4);
+            assert_eq!(l.points(limit).count(), limit.min(solutions.len()));
+
+            if !l.is_empty() {
+                for _ in 0..16 {
+                    assert!(&a * &l.sample(&mut rng) == b, "The sample is not a solution");
+                }
+            }
+        }
+    }
+
+    /// Checks that the short and sparse points are solutions
+    /// that are no larger than the offsets of the lattice.
+    fn check_small_points<T: UniformNum>(dim: usize)
+        where Standard: Distribution<T>
+    {
+        let abs = |x: &Vector<T>| x.iter().map(|e| e.to_i128().unsigned_abs()).sum::<u128>();
+        let nz = |x: &Vector<T>| x.iter().filter(|e| !e.is_zero()).count() as u128;
+
+        let mut rng = ChaCha8Rng::seed_from_u64(T::bits() as u64);
+        for _ in 0..64 {
+            let rows = rng.gen_range(1..=3);
+            let (a, b) = rand_system::<T, _>(rows, dim, &mut rng);
+            let l = solve_congruences(a.clone(), &b);
+            if l.is_empty() {
+                continue;
+            }
+
+            let c = l.clone().canonical();
+            let short = l.small_point(false);
+            let sparse = l.small_point(true);
+            assert!(&a * &short == b && &a * &sparse == b, "The small point is not a solution");
+            for o in [&l.offset, &c.offset] {
+                assert!((abs(&short), nz(&short)) <= (abs(o), nz(o)), "The short point is larger than the offset");
+                assert!((nz(&sparse), abs(&sparse)) <= (nz(o), abs(o)), "The sparse point is larger than the offset");
+            }
+        }
+    }
+
+    #[test]
+    fn small_points() {
+        check_small_points::<W>(6);
+        check_small_points::<Wrapping<u64>>(6);
+        UInt::with_width(3, || check_small_points::<UInt>(6));
+    }
+
+    /// Samples from a lattice with a redundant basis
+    /// and checks that every vector appears about as often.
+    #[test]
+    fn sample_uniform() {
+        UInt::with_width(3, || {
+            let v = |e: &[u128]| Vector::from_slice(&e.iter().map(|&e| UInt::new(e)).collect::<Vec<_>>());
+            let l = AffineLattice {
+                offset: v(&[1, 0]),
+                basis: vec![v(&[2, 4]), v(&[2, 4]), v(&[4, 0]), v(&[0, 4])],
+                modulus: UInt::new(0),
+            };
+
+            let n = l.count().unwrap() as usize;
+            let mut counts = std::collections::HashMap::new();
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            for _ in 0..1000 * n {
+                let x = l.sample(&mut rng);
+                *counts.entry((x[0], x[1])).or_insert(0usize) += 1;
+            }
+
+            assert_eq!(counts.len(), n);
+            for c in counts.values() {
+                assert!((900..1100).contains(c), "Not uniform: {:?}", counts);
+            }
+        });
+    }
+
+    #[test]
+    fn points_u8() {
+        check_points::<W>(2);
+    }
+
+    #[test]
+    fn points_3_bits() {
+        UInt::with_width(3, || check_points::<UInt>(4));
+    }
+
+    #[test]
+    fn count_overflow() {
+        let l = solve_congruences(Matrix::<Wrapping<u64>>::zero(1, 3), &Vector::zero(1));
+        assert_eq!(l.count_log2(), Some(192));
+        assert_eq!(l.count(), None);
+        assert!(solve_congruences(matrix([[2]]), &vector(&[1])).count() == Some(0));
+    }
+
+    #[test]
+    fn lattices_u8() {
+        check_lattices::<W>(2, 4);
+    }
+
+    #[test]
+    fn lattices_3_bits() {
+        UInt::with_width(3, || check_lattices::<UInt>(4, 32));
+    }
+
+    #[test]
+    fn smith_normal_form_u8() {
+        check_snf_counts::<W>(2);
+    }
+
+    #[test]
+    fn smith_normal_form_3_bits() {
+        UInt::with_width(3, || check_snf_counts::<UInt>(4));
+    }
+
+    #[test]
+    fn smith_normal_form_wide() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..16 {
+            check_snf(&rand_matrix::<Wrapping<u64>, _>(4, 5, &mut rng));
+            check_snf(&rand_matrix::<Wrapping<u128>, _>(5, 3, &mut rng));
+        }
+    }
+
+    fn matrix<const R: usize, const C: usize>(a: [[u8; C]; R]) -> Matrix<W> {
+        Matrix::from_array(a.map(|r| r.map(Wrapping)))
+    }
+
+    fn vector(v: &[u8]) -> Vector<W> {
+        Vector::from_slice(&v.iter().copied().map(Wrapping).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn records_modulus() {
+        let a = matrix([[2, 3], [4, 1]]);
+        let b = vector(&[1, 2]);
+        let l = solve_congruences_mod(a.clone(), &b, Wrapping(15));
+        assert_eq!(l.modulus, Wrapping(15));
+        assert!(!l.is_empty());
+        assert_eq!(solve_congruences(a, &b).modulus, Wrapping(0));
+        assert_eq!(solve_congruences_mod(matrix([[3]]), &vector(&[1]), Wrapping(6)).modulus, Wrapping(6));
+    }
+
+    /// Checks the lattice operations mod moduli that aren't powers of two against brute force.
+    #[test]
+    fn other_moduli() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for n in [6u8, 9, 10, 12, 15] {
+            let n = Wrapping(n);
+            let all: Vec<_> = (0..n.0)
+                .flat_map(|i| (0..n.0).map(move |j| vector(&[i, j])))
+                .collect();
+
+            // Ax mod n.
+            let mul = |a: &Matrix<W>, x: &Vector<W>| {
+                let mut y = Vector::zero(a.rows);
+                for i in 0..a.rows {
+                    for j in 0..a.cols {
+                        y[i] = add_mod(y[i], mul_mod(a[(i, j)], x[j], n), n);
+                    }
+                }
+                y
+            };
+            let rand_system = |rng: &mut ChaCha8Rng| {
+                let rows = rng.gen_range(1..=2);
+                let mut a = Matrix::zero(rows, 2);
+                for i in 0..rows {
+                    for j in 0..2 {
+                        a[(i, j)] = Wrapping(rng.gen_range(0..n.0));
+                    }
+                }
+                let b = match rng.gen::<bool>() {
+                    true => mul(&a, &all[rng.gen_range(0..all.len())]),
+                    false => Vector::from_slice(&(0..rows)
+                        .map(|_| Wrapping(rng.gen_range(0..n.0)))
+                        .collect::<Vec<_>>()),
+                };
+                (a, b)
+            };
+
+            for _ in 0..32 {
+                let (a1, b1) = rand_system(&mut rng);
+                let (a2, b2) = rand_system(&mut rng);
+                let l1 = solve_congruences_mod(a1.clone(), &b1, n);
+                let l2 = solve_congruences_mod(a2.clone(), &b2, n);
+                let in1: Vec<_> = all.iter().filter(|x| mul(&a1, x) == b1).collect();
+                let in2: Vec<_> = all.iter().filter(|x| mul(&a2, x) == b2).collect();
+
+                let count = in1.len() as u128;
+                assert_eq!(l1.count(), Some(count));
+                match l1.count_log2() {
+                    Some(k) => assert_eq!(1 << k, count),
+                    None => assert!(count == 0 || !count.is_power_of_two()),
+                }
+
+                let mut points: Vec<_> = l1.points(usize::MAX).collect();
+                assert_eq!(points.len() as u128, count);
+                points.sort_by_key(|x| (x[0], x[1]));
+                points.dedup();
+                assert!(points.iter().collect::<Vec<_>>() == in1, "The points are not the solutions");
+
+                let i = l1.intersection(&l2);
+                let s = l1.sum(&l2);
+                for x in &all {
+                    let sum = in1.iter().any(|y| {
+                        let z = vector(&[(n - y[0] + x[0]).0 % n.0, (n - y[1] + x[1]).0 % n.0]);
+                        in2.contains(&&z)
+                    });
+                    assert_eq!(l1.contains(x), in1.contains(&x));
+                    assert_eq!(i.contains(x), in1.contains(&x) && in2.contains(&x));
+                    assert_eq!(s.contains(x), sum);
+                }
+                assert_eq!(i.modulus, n);
+                assert_eq!(s.modulus, n);
+
+                if !l1.is_empty() {
+                    assert!(mul(&a1, &l1.sample(&mut rng)) == b1, "The sample is not a solution");
+                    assert!(mul(&a1, &l1.small_point(false)) == b1, "The short point is not a solution");
+                    assert!(mul(&a1, &l1.small_point(true)) == b1, "The sparse point is not a solution");
+                }
+
+                // The same lattice with a redundant basis vector.
+                let mut l3 = l1.clone();
+                if let Some(b) = l1.basis.first() {
+                    l3.basis.push(scale(b, Wrapping(rng.gen_range(0..n.0)), n));
+                }
+                assert!(l3 == l1, "Equal lattices are different");
+                assert!(l1 == l1.clone().canonical(), "The canonical form is a different lattice");
+                assert!(l1.is_empty() || l1 != solve_congruences(a1.clone(), &b1));
+            }
+        }
+    }
+
+    #[test]
+    fn crt() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for (n1, n2) in [(3, 5), (4, 3), (9, 10), (1, 7)] {
+            let (n1, n2) = (Wrapping(n1), Wrapping(n2));
+            for _ in 0..32 {
+                let (a, b) = rand_system::<W, _>(rng.gen_range(1..=3), 3, &mut rng);
+                let l1 = solve_congruences_mod(a.clone(), &b, n1);
+                let l2 = solve_congruences_mod(a.clone(), &b, n2);
+                let l = solve_congruences_mod(a, &b, n1 * n2);
+                assert!(l1.crt(&l2) == l, "The combined lattice is different");
+                assert!(l2.crt(&l1) == l, "The combined lattice is different");
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not coprime")]
+    fn crt_not_coprime() {
+        let l = solve_congruences_mod(matrix([[1]]), &vector(&[1]), Wrapping(6));
+        l.crt(&l);
+    }
+}
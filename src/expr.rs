@@ -1,7 +1,10 @@
 use std::rc::Rc;
 use std::fmt::Write;
-use std::collections::BTreeSet;
-use crate::{numbers::{UnsignedInt, int_from_it}, printer::Printer};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use crate::numbers::{UnsignedInt, UniformNum, int_from_str};
+use crate::parser::{Lexer, Token, ParseError};
+use crate::printer::{Printer, c_type, c_const, c_compute_type, c_is_bitint, rust_int, rust_bits, js_bigint, mask};
+use crate::uniform_expr::Valuation;
 
 #[derive(Debug, Clone)]
 pub enum Expr<T> {
@@ -34,22 +37,31 @@ impl<T: UnsignedInt> Expr<T> {
     /// Returns all variables in the expression.
     pub fn vars(&self) -> Vec<String> {
         let mut v = BTreeSet::new();
-        self.vars_impl(&mut v);
+        self.vars_impl(&mut v, &mut HashSet::new());
         v.into_iter().collect()
     }
 
-    fn vars_impl(&self, v: &mut BTreeSet<String>) {
+    fn vars_impl(
+        &self, v: &mut BTreeSet<String>, visited: &mut HashSet<*const Self>
+    ) {
+        // Shared subexpressions only need to be visited once.
+        let mut rec = |e: &Rc<Self>| {
+            if Rc::strong_count(e) == 1 || visited.insert(Rc::as_ptr(e)) {
+                e.vars_impl(v, visited);
+            }
+        };
+
         match self {
             Expr::Const(_) => {},
             Expr::Var(name) => drop(v.insert(name.clone())),
-            Expr::Neg(e) | Expr::Not(e) => e.vars_impl(v),
+            Expr::Neg(e) | Expr::Not(e) => rec(e),
 
             Expr::Add(l, r) | Expr::Sub(l, r) | Expr::Mul(l, r)
             | Expr::Div(l, r) | Expr::Mod(l, r) | Expr::And(l, r)
             | Expr::Or(l, r) | Expr::Xor(l, r) | Expr::Shl(l, r)
             | Expr::Shr(l, r) => {
-                l.vars_impl(v);
-                r.vars_impl(v);
+                rec(l);
+                rec(r);
             }
         }
     }
@@ -103,7 +115,7 @@ impl<T: UnsignedInt> Expr<T> {
 
     /// Returns the precedence of a binary operator.
     /// All operators are treated as being left associative.
-    fn precedence(&self) -> usize {
+    pub(crate) fn precedence(&self) -> usize {
         use Expr::*;
         match self {
             Or(_, _) => 1,
@@ -117,83 +129,83 @@ impl<T: UnsignedInt> Expr<T> {
         }
     }
 
-    /// Parse an expression from a string.
-    /// Can't parse shifts at the moment.
-    /// Closing brackets are a bit broken.
-    pub fn from_string<U: ToString>(s: U) -> Result<Expr<T>, String> {
-        let mut s = s.to_string();
-        s.retain(|c| !c.is_whitespace());
-        let mut it = s.chars().peekable();
+    /// Is `a op (b op' c)` the same as `a op b op' c`,
+    /// where `op` is this operator and `op'` the one of `r`.
+    fn is_associative_with(&self, r: &Self) -> bool {
+        use Expr::*;
+        matches!((self, r),
+            (Add(_, _), Add(_, _) | Sub(_, _))
+            | (Mul(_, _), Mul(_, _))
+            | (And(_, _), And(_, _))
+            | (Or(_, _), Or(_, _))
+            | (Xor(_, _), Xor(_, _)))
+    }
+}
+
+impl<T: UniformNum> Expr<T> {
+    /// Is this a division, remainder or shift whose result is undefined
+    /// in some languages for some values of the right operand,
+    /// i.e. division by 0 or -1 if `signed` is set and shifts by the width or more.
+    /// Constant right operands for which it is defined don't need a guard.
+    fn needs_guard(&self, signed: bool) -> bool {
+        use Expr::*;
+        match self {
+            Div(_, r) | Mod(_, r) => match r.as_ref() {
+                Const(c) => c.is_zero() || signed && (T::zero() - *c).is_one(),
+                _ => true,
+            },
+            Shl(_, r) | Shr(_, r) => match r.as_ref() {
+                Const(c) => shift_amount(*c).is_none(),
+                _ => true,
+            },
+            _ => false,
+        }
+    }
 
-        Self::parse(&mut it, 0)
+    /// Parse an expression from a string.
+    pub fn from_string<U: AsRef<str>>(s: U) -> Result<Expr<T>, ParseError> {
+        let mut l = Lexer::new(s.as_ref());
+        let e = Self::parse(&mut l, 0)?;
+        l.expect_eof(&["operator", "end of input"])?;
+        Ok(e)
     }
 
     // pre 0: parse as much as possible
     // ...
     // pre 15: parse as little as possible
-    fn parse(
-        it: &mut std::iter::Peekable<std::str::Chars>,
-        pre: usize
-    ) -> Result<Self, String> {
+    pub(crate) fn parse(l: &mut Lexer, pre: usize) -> Result<Self, ParseError> {
         use Expr::*;
 
-        let mut c = *it.peek()
-            .ok_or_else(|| "Unexpected end of input".to_owned())?;
-
-        let mut e = if c == '(' {
-            it.next();
-            let e = Self::parse(it, 0)?;
-            match it.next() {
-                Some(')') => e,
-                _ => return Err("Closing bracket missing".into()),
-            }
-        } else if c == '~' {
-            it.next();
-            let e = Self::parse(it, 15)?;
-            Not(Rc::new(e))
-        } else if c == '-' {
-            it.next();
-            let e = Self::parse(it, 15)?;
-            Neg(Rc::new(e))
-        } else if c.is_alphabetic() {
-            it.next();
-            let mut var = String::from(c);
-            loop {
-                let Some(c) = it.peek() else {
-                    break
-                };
-
-                if !c.is_alphanumeric() {
-                    break
-                }
-
-                var.push(*c);
-                it.next();
-            }
-
-            Var(var)
-        } else if c.is_ascii_digit() {
-            // This can't panic because we check that
-            // the character is an ascii digit.
-            let num = int_from_it(it).unwrap();
-            Const(num)
-        } else {
-            return Err("Unrecognized character".into());
+        let mut e = match l.next_token() {
+            (Token::Sym("("), _) => {
+                let e = Self::parse(l, 0)?;
+                l.expect(")", &["operator", "')'"])?;
+                e
+            },
+            (Token::Sym("~"), _) => Not(Rc::new(Self::parse(l, 15)?)),
+            (Token::Sym("-"), _) => Neg(Rc::new(Self::parse(l, 15)?)),
+            (Token::Ident(v), _) => Var(v.to_owned()),
+            (Token::Num(n), span) => Const(int_from_str(n).map_err(|e|
+                ParseError::new(Token::Num(n), span, &[e])
+            )?),
+            (t, span) => return Err(ParseError::new(
+                t, span, &["variable", "constant", "'('", "'~'", "'-'"]
+            )),
         };
 
         loop {
-            let Some(c) = it.peek().cloned() else {
+            let (Token::Sym(op), _) = l.peek() else {
                 return Ok(e)
             };
 
-            let op_pre = match c {
-                '|' => 1,
-                '^' => 2,
-                '&' => 3,
-                '+' | '-' => 5,
-                '*' | '/' | '%' => 6,
-                ')' => return Ok(e),
-                _ => return Err("Unknown operator".into()),
+            let op_pre = match op {
+                "|" => 1,
+                "^" => 2,
+                "&" => 3,
+                "<<" | ">>" => 4,
+                "+" | "-" => 5,
+                "*" | "/" | "%" => 6,
+                _ => return Ok(e),
             };
 
             if op_pre <= pre {
@@ -203,125 +215,191 @@ impl<T: UnsignedInt> Expr<T> {
             // If the current operators precedence is higher than
             // the one whose subexpression we are currently parsing
             // then we need to finish this operator first.
-            it.next();
-            let rhs = Rc::new(Self::parse(it, op_pre)?);
+            l.next_token();
+            let rhs = Rc::new(Self::parse(l, op_pre)?);
             let lhs = Rc::new(e);
-            e = match c {
-                '+' => Add(lhs, rhs),
-                '-' => Sub(lhs, rhs),
-                '*' => Mul(lhs, rhs),
-                '/' => Div(lhs, rhs),
-                '%' => Mod(lhs, rhs),
-                '&' => And(lhs, rhs),
-                '|' => Or(lhs, rhs),
-                '^' => Xor(lhs, rhs),
-                c => panic!("Unknown operator: {c}"),
+            e = match op {
+                "+" => Add(lhs, rhs),
+                "-" => Sub(lhs, rhs),
+                "*" => Mul(lhs, rhs),
+                "/" => Div(lhs, rhs),
+                "%" => Mod(lhs, rhs),
+                "&" => And(lhs, rhs),
+                "|" => Or(lhs, rhs),
+                "^" => Xor(lhs, rhs),
+                "<<" => Shl(lhs, rhs),
+                ">>" => Shr(lhs, rhs),
+                op => panic!("Unknown operator: {op}"),
             };
-        };
+        }
     }
 
     /// Prints the expression while avoiding reprinting
     /// common subexpressions by assigning them to variables.
     /// This only works if the Rc's used in the expression
     /// are not shared with other expressions.
-    pub fn print_as_fn(&self, printer: Printer) -> String {
-        assert!(printer != Printer::Tex,
-            "Tex printing is not supported for general expressions.");
+    ///
+    /// For Tex, the common subexpressions are printed
+    /// as definitions in an `aligned` environment.
+    ///
+    /// The functions compute what [`Expr::eval`] does
+    /// for division by zero and shifts by the width or more,
+    /// which are undefined, panic or differ in the languages,
+    /// by checking the right operand.
+    /// The C function computes with `unsigned` for 8 and 16 bits,
+    /// because narrower integers are promoted to int, which can overflow.
+    ///
+    /// If `signed` is set, division, remainder and right shifts are signed
+    /// and constants are printed as signed numbers.
+    /// The C function takes and returns signed integers,
+    /// but computes with unsigned ones, because signed overflow is undefined.
+    /// The Python and JavaScript functions take and return signed integers too.
+    pub fn print_as_fn(&self, printer: Printer, signed: bool) -> String {
+        self.print_fn_impl(printer, signed, None)
+    }
+
+    /// Prints a function that returns whether the expression is equal to `c`,
+    /// which can be used as a condition.
+    /// Only the default, C and Rust printers are supported.
+    /// See [`Expr::print_as_fn`] for the other arguments.
+    pub fn print_as_condition(&self, printer: Printer, c: T, signed: bool) -> String {
+        assert!(printer.supports_conditions(),
+            "The printer does not support conditions.");
+        self.print_fn_impl(printer, signed, Some(c))
+    }
 
+    /// Prints the function, which compares the result to `cmp` if it is set.
+    fn print_fn_impl(&self, printer: Printer, signed: bool, cmp: Option<T>) -> String {
         let mut input = self.vars();
-        // This shouldn't really be done here,
-        // but I can't be bothered.
-        input.sort_by(|l, r| {
-            if l.starts_with("aux") {
-                if r.starts_with("aux") {
-                    l.cmp(r)
-                } else {
-                    std::cmp::Ordering::Greater
-                }
-            } else if r.starts_with("aux") {
-                std::cmp::Ordering::Less
-            } else {
-                l.cmp(r)
-            }
-        });
+        sort_args(&mut input);
+
+        if printer == Printer::LlvmIr {
+            return self.print_llvm(&input, signed);
+        } else if printer == Printer::SmtLib {
+            return self.print_smt_fn("f", &input, signed);
+        }
 
         // Stores a mapping of (sub)expressions to variables.
         let mut vars = Vec::new();
 
-        let l = self.print_simple_impl(&mut vars, printer);
+        let l = self.print_simple_impl(&mut vars, printer, signed);
 
         let mut s = String::new();
         if printer == Printer::Default {
-            for (_, var, init) in vars.iter().rev() {
-                write!(&mut s, "{} = {}", var, init);
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "{} = {}", var, init);
+            }
+            s += &l;
+            if let Some(c) = cmp {
+                write!(&mut s, " == {}", c);
             }
         } else if printer == Printer::C {
-            let ty = match std::mem::size_of::<T>() {
-                1 => "uint8_t",
-                2 => "uint16_t",
-                4 => "uint32_t",
-                8 => "uint64_t",
-                16 => "uint128_t",
-                _ => panic!("Unknown type."),
-            };
+            let ty = c_compute_type(T::bits());
+            let arg_ty = c_type(T::bits(), signed);
+            let ret_ty = if cmp.is_some() { "bool".to_owned() } else { arg_ty.clone() };
 
-            write!(&mut s, "{} f(", ty);
-            for v in &input {
-                write!(&mut s, "{} {}, ", ty, v);
-            }
-            s.pop();
-            s.pop();
-            write!(&mut s, ") {{\n");
+            write!(&mut s, "{} f(", ret_ty);
+            let args: Vec<_> = input.iter()
+                .map(|v| format!("{} {}", arg_ty, v))
+                .collect();
+            s += &args.join(", ");
+            writeln!(&mut s, ") {{");
 
-            for (_, var, init) in vars.iter().rev() {
-                write!(&mut s, "\t{} {} = {};\n", ty, var, init);
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "\t{} {} = {};", ty, var, init);
             }
 
+            // The cast truncates integers that were promoted to int.
+            let l = match cmp {
+                Some(c) => format!("({})({}) == {}", ty, l, c_const(c.to_u128(), T::bits())),
+                None => l,
+            };
+
             write!(&mut s, "\treturn {};\n}}", &l);
         } else if printer == Printer::Rust {
-            let ty = match std::mem::size_of::<T>() {
-                1 => "Wrapping<u8>",
-                2 => "Wrapping<u16>",
-                4 => "Wrapping<u32>",
-                8 => "Wrapping<u64>",
-                16 => "Wrapping<u128>",
-                _ => panic!("Unknown type."),
-            };
+            let ty = format!("Wrapping<{}>", rust_int(T::bits(), signed));
 
             write!(&mut s, "fn f(");
-            for v in &input {
-                write!(&mut s, "{}: {}, ", v, ty);
-            }
-            s.pop();
-            s.pop();
-            write!(&mut s, ") -> {} {{\n", ty);
+            let args: Vec<_> = input.iter()
+                .map(|v| format!("{}: {}", v, ty))
+                .collect();
+            s += &args.join(", ");
+            let ret_ty = if cmp.is_some() { "bool" } else { &ty };
+            writeln!(&mut s, ") -> {} {{", ret_ty);
 
-            for (_, var, init) in vars.iter().rev() {
-                write!(&mut s, "\tlet {} = {};\n", var, init);
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "\tlet {} = {};", var, init);
             }
 
+            let l = if printer.needs_mask(T::bits()) {
+                Self::mask(&l, self.precedence(), printer, signed)
+            } else {
+                l
+            };
+
+            let l = match cmp {
+                Some(c) if signed => format!("{} == Wrapping({})", l, c.to_i128()),
+                Some(c) => format!("{} == Wrapping({})", l, c),
+                None => l,
+            };
+
             write!(&mut s, "\t{}\n}}", &l);
+        } else if printer == Printer::Python {
+            writeln!(&mut s, "def f({}):", input.join(", "));
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "    {} = {}", var, init);
+            }
+            write!(&mut s, "    return {}", Self::mask(&l, self.precedence(), printer, signed));
+        } else if printer == Printer::JavaScript {
+            writeln!(&mut s, "function f({}) {{", input.join(", "));
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "\tconst {} = {};", var, init);
+            }
+            write!(&mut s, "\treturn {};\n}}", Self::mask(&l, self.precedence(), printer, signed));
+        } else if printer == Printer::Tex {
+            if vars.is_empty() {
+                return l;
+            }
+
+            s += "\\begin{aligned}\n";
+            for (_, var, init) in &vars {
+                writeln!(&mut s, "{} &= {} \\\\", var, init);
+            }
+            write!(&mut s, "f({}) &= {}\n\\end{{aligned}}", input.join(", "), &l);
         } else {
-            assert!(false, "Unsupported printer.");
+            panic!("Unsupported printer.");
         }
 
         s
     }
 
-    fn print_simple_rc(
+    pub(crate) fn print_simple_rc(
         e: &Rc<Self>,
         vars: &mut Vec<(*const Self, String, String)>,
-        printer: Printer
+        printer: Printer,
+        signed: bool
     ) -> String {
         // If there is only one reference then just print it.
         if Rc::strong_count(e) == 1 {
-            return e.print_simple_impl(vars, printer);
+            return e.print_simple_impl(vars, printer, signed);
         }
 
+        Self::print_var_rc(e, vars, printer, signed)
+    }
+
+    /// Prints the expression as a variable that is defined when it is first used,
+    /// so it is only computed once.
+    /// Variables and constants are printed as they are.
+    fn print_var_rc(
+        e: &Rc<Self>,
+        vars: &mut Vec<(*const Self, String, String)>,
+        printer: Printer,
+        signed: bool
+    ) -> String {
         // We don't want to assign a variable to a variable
         // so there is this shortcut here.
-        if let Expr::Var(v) = &**e {
-            return format!("{}", *v);
+        if let Expr::Var(_) | Expr::Const(_) = &**e {
+            return e.print_simple_impl(vars, printer, signed);
         }
 
         let ptr = Rc::as_ptr(e);
@@ -331,15 +409,25 @@ impl<T: UnsignedInt> Expr<T> {
         if let Some(v) = var {
             v.1.clone()
         } else {
-            let v = format!("var{}", vars.len());
-
-            // Push everything.
-            vars.push((ptr, v.clone(), String::new()));
-
-            let idx = vars.len() - 1;
-
             // Get the initializer for the variable.
-            vars[idx].2 = e.print_simple_impl(vars, printer);
+            // Python and JavaScript's numbers would grow without bound.
+            let init = e.print_simple_impl(vars, printer, signed);
+            let init = match printer {
+                Printer::Python | Printer::JavaScript => {
+                    Self::mask(&init, e.precedence(), printer, false)
+                },
+                _ => init,
+            };
+
+            // The variable is only pushed after the ones its initializer uses,
+            // so the variables can be defined in order.
+            // The underscore keeps the names apart from the inputs.
+            let v = if printer == Printer::Tex {
+                format!("\\text{{var}}_{{{}}}", vars.len())
+            } else {
+                format!("var_{}", vars.len())
+            };
+            vars.push((ptr, v.clone(), init));
 
             // Return just the variable name.
             v
@@ -350,60 +438,969 @@ impl<T: UnsignedInt> Expr<T> {
     fn print_simple_impl(
         &self,
         vars: &mut Vec<(*const Self, String, String)>,
-        printer: Printer
+        printer: Printer,
+        signed: bool
     ) -> String {
+        // The integers in some languages are wider than T,
+        // so the high bits have to be cleared when they would matter.
+        let mask = printer.needs_mask(T::bits());
+        let python = printer == Printer::Python;
+        let js = printer == Printer::JavaScript;
+        let js_number = js && !js_bigint(T::bits());
+        let tex = printer == Printer::Tex;
+        // The signed C function computes with unsigned integers
+        // and only casts to signed ones for the signed operations.
+        let c_signed = printer == Printer::C && signed;
+        // Narrow C integers would be promoted to int, which can overflow.
+        let c_narrow = printer == Printer::C && mask;
+        // Some divisions and shifts need a guard to compute what `eval` does.
+        // Python's right shifts already do for all shift amounts.
+        // The signed operations in Python and JavaScript are printed there too.
+        let guard = match printer {
+            Printer::Default | Printer::Tex => false,
+            Printer::Python | Printer::JavaScript if signed => true,
+            Printer::Python => !matches!(self, Shr(_, _)),
+            _ => self.needs_guard(signed),
+        };
+        use Expr::*;
+
+        // Does an operand need parentheses.
+        // The right operand also needs them if it has the same precedence,
+        // unless the operators can be regrouped, like in `a + (b - c)`.
+        // In Tex, the bitwise operators don't have a precedence
+        // so their operands are always parenthesized.
+        let needs_parens = |e: &Rc<Self>, right: bool| {
+            let (pred, epred) = (self.precedence(), e.precedence());
+            Rc::strong_count(e) == 1 && (
+                pred > epred
+                || right && pred == epred && !self.is_associative_with(e)
+                || tex && pred < 5 && epred < 255
+            )
+        };
+
         // Print a binary operation.
         let bin_op = |
             op: &str, l: &Rc<Self>, r: &Rc<Self>,
             vars: &mut Vec<(*const Self, String, String)>
         | {
-            let pred = self.precedence();
+            let l = if needs_parens(l, false) {
+                format!("({})", Self::print_simple_rc(l, vars, printer, signed))
+            } else {
+                Self::print_simple_rc(l, vars, printer, signed)
+            };
 
-            let l = if pred > l.precedence() && Rc::strong_count(l) == 1 {
-                format!("({})", Self::print_simple_rc(l, vars, printer))
+            let r = if needs_parens(r, true) {
+                format!("({})", Self::print_simple_rc(r, vars, printer, signed))
             } else {
-                format!("{}", Self::print_simple_rc(l, vars, printer))
+                Self::print_simple_rc(r, vars, printer, signed)
             };
 
-            let r = if pred > r.precedence() && Rc::strong_count(r) == 1 {
-                format!("({})", Self::print_simple_rc(r, vars, printer))
+            format!("{} {} {}", l, op, r)
+        };
+
+        // Print a constant.
+        let print_const = |i: T| Self::print_const(i, printer, signed);
+
+        // Is the constant negative and can it be negated.
+        // The minimum can't, so `a + -128 * b` stays as it is.
+        let is_neg = |c: T| c.print_negative() && !(T::zero() - c).print_negative();
+
+        // Is the expression `-1 * e`.
+        let is_neg_one = |c: &Rc<Self>| signed && Rc::strong_count(c) == 1
+            && matches!(c.as_ref(), Const(c) if (T::zero() - *c).is_one());
+
+        // Print an addition or subtraction.
+        // For signed integers, `a + -3 * b` is printed as `a - 3 * b`
+        // and `a + -1 * b` as `a - b`.
+        let add_op = |
+            op: &str, neg_op: &str, l: &Rc<Self>, r: &Rc<Self>,
+            vars: &mut Vec<(*const Self, String, String)>
+        | {
+            let neg = signed && Rc::strong_count(r) == 1 && match r.as_ref() {
+                Const(c) => is_neg(*c),
+                Mul(c, _) => matches!(c.as_ref(), Const(c) if is_neg(*c)),
+                _ => false,
+            };
+            if !neg {
+                return bin_op(op, l, r, vars);
+            }
+
+            let l = if needs_parens(l, false) {
+                format!("({})", Self::print_simple_rc(l, vars, printer, signed))
             } else {
-                format!("{}", Self::print_simple_rc(r, vars, printer))
+                Self::print_simple_rc(l, vars, printer, signed)
             };
 
+            let r = match r.as_ref() {
+                Const(c) => print_const(T::zero() - *c),
+                Mul(c, m) if is_neg_one(c) => {
+                    let pred = m.precedence();
+                    if Rc::strong_count(m) == 1 && (pred < 5 || pred == 5 && neg_op == "-") {
+                        format!("({})", Self::print_simple_rc(m, vars, printer, signed))
+                    } else {
+                        Self::print_simple_rc(m, vars, printer, signed)
+                    }
+                },
+                // The product of two numbers isn't exact in floating point.
+                Mul(c, m) if js_number => {
+                    let Const(c) = c.as_ref() else { unreachable!() };
+                    format!("Math.imul({}, {})", print_const(T::zero() - *c),
+                        Self::print_simple_rc(m, vars, printer, signed))
+                },
+                Mul(c, m) => {
+                    let Const(c) = c.as_ref() else { unreachable!() };
+                    let m_pred = m.precedence();
+                    let m = if Rc::strong_count(m) == 1 && (m_pred < 6
+                        || m_pred == 6 && !r.is_associative_with(m)) {
+                        format!("({})", Self::print_simple_rc(m, vars, printer, signed))
+                    } else {
+                        Self::print_simple_rc(m, vars, printer, signed)
+                    };
+                    let mul = if tex { "\\cdot" } else { "*" };
+                    format!("{} {} {}", print_const(T::zero() - *c), mul, m)
+                },
+                _ => unreachable!(),
+            };
+
+            format!("{} {} {}", l, neg_op, r)
+        };
+
+        // Print a binary operation whose operands need to be masked,
+        // because the printer's integers are wider.
+        let masked_bin_op = |
+            op: &str, l: &Rc<Self>, r: &Rc<Self>,
+            vars: &mut Vec<(*const Self, String, String)>
+        | {
+            let l = Self::mask_rc(l, vars, printer, signed);
+            let r = Self::mask_rc(r, vars, printer, signed);
             format!("{} {} {}", l, op, r)
         };
 
+        // Print an operand of a signed operation in C.
+        // The variables already are signed integers.
+        let c_signed_operand = |
+            e: &Rc<Self>, vars: &mut Vec<(*const Self, String, String)>
+        | {
+            let ty = c_type(T::bits(), true);
+            match e.as_ref() {
+                Var(v) => v.clone(),
+                _ if Rc::strong_count(e) > 1 => {
+                    format!("({}){}", ty, Self::print_simple_rc(e, vars, printer, signed))
+                },
+                _ => format!("({})({})", ty, Self::print_simple_rc(e, vars, printer, signed)),
+            }
+        };
+
+        // Print a signed operation in C.
+        // The shift amount is unsigned.
+        let c_signed_op = |
+            op: &str, l: &Rc<Self>, r: &Rc<Self>,
+            vars: &mut Vec<(*const Self, String, String)>
+        | {
+            let l = c_signed_operand(l, vars);
+            let r = if op != ">>" {
+                c_signed_operand(r, vars)
+            } else if needs_parens(r, true) {
+                format!("({})", Self::print_simple_rc(r, vars, printer, signed))
+            } else {
+                Self::print_simple_rc(r, vars, printer, signed)
+            };
+            format!("({})({} {} {})", c_type(T::bits(), false), l, op, r)
+        };
+
         // Print a unary operation.
         let un_op = |
             op: &str, i: &Rc<Self>,
             vars: &mut Vec<(*const Self, String, String)>
         | {
-            if self.precedence() > i.precedence() && Rc::strong_count(i) == 1 {
-                format!("{}({})", op, Self::print_simple_rc(i, vars, printer))
+            // `--x` would be a decrement in C.
+            let s = Self::print_simple_rc(i, vars, printer, signed);
+            if self.precedence() > i.precedence() && Rc::strong_count(i) == 1
+                || s.starts_with('-') {
+                format!("{}({})", op, s)
             } else {
-                format!("{}{}", op, Self::print_simple_rc(i, vars, printer))
+                format!("{}{}", op, s)
             }
         };
 
-        use Expr::*;
+        // Print a shift if the printer's integers are wider.
+        // Then the shift amount always has to be masked
+        // and so does the shifted value for right shifts.
+        let masked_shift = |
+            op: &str, l: &Rc<Self>, r: &Rc<Self>,
+            vars: &mut Vec<(*const Self, String, String)>
+        | {
+            let l = if op != "<<" {
+                Self::mask_rc(l, vars, printer, signed)
+            } else if needs_parens(l, false) {
+                format!("({})", Self::print_simple_rc(l, vars, printer, signed))
+            } else {
+                Self::print_simple_rc(l, vars, printer, signed)
+            };
+
+            let r = match r.as_ref() {
+                Const(_) => Self::print_simple_rc(r, vars, printer, signed),
+                _ => Self::mask_rc(r, vars, printer, false),
+            };
+            format!("{} {} {}", l, op, r)
+        };
+
+        // Print a shift for rust.
+        // `Wrapping` can only be shifted by a `usize`.
+        let rust_shift = |
+            op: &str, l: &Rc<Self>, r: &Rc<Self>,
+            vars: &mut Vec<(*const Self, String, String)>
+        | {
+            let l = if op == ">>" && mask {
+                Self::mask_rc(l, vars, printer, signed)
+            } else if needs_parens(l, false) {
+                format!("({})", Self::print_simple_rc(l, vars, printer, signed))
+            } else {
+                Self::print_simple_rc(l, vars, printer, signed)
+            };
+
+            let r = if let Expr::Const(i) = r.as_ref() {
+                format!("{}", i)
+            } else if mask {
+                format!("{}.0 as usize", Self::mask_rc(r, vars, printer, false))
+            } else if r.precedence() == 256 || Rc::strong_count(r) > 1 {
+                format!("{}.0 as usize", Self::print_simple_rc(r, vars, printer, signed))
+            } else {
+                format!("({}).0 as usize", Self::print_simple_rc(r, vars, printer, signed))
+            };
+
+            format!("{} {} {}", l, op, r)
+        };
+
         match self {
-            Const(i) if printer == Printer::Rust => format!("Wrapping({})", i),
-            Const(i) => format!("{}", i),
-            Var(n) => format!("{}", n),
-            Add(l, r) => bin_op("+", l, r, vars),
-            Sub(l, r) => bin_op("-", l, r, vars),
+            Const(i) => print_const(*i),
+            Var(n) if c_signed || c_narrow => format!("({}){}", c_compute_type(T::bits()), n),
+            Var(n) => n.clone(),
+            Add(l, r) => add_op("+", "-", l, r, vars),
+            Sub(l, r) => add_op("-", "+", l, r, vars),
+            Mul(l, r) if is_neg_one(l) => un_op("-", r, vars),
+            // The product of two numbers isn't exact in floating point.
+            Mul(l, r) if js_number => format!("Math.imul({}, {})",
+                Self::print_simple_rc(l, vars, printer, signed),
+                Self::print_simple_rc(r, vars, printer, signed)),
+            Mul(l, r) if tex => bin_op("\\cdot", l, r, vars),
             Mul(l, r) => bin_op("*", l, r, vars),
+            Div(_, _) | Mod(_, _) | Shl(_, _) | Shr(_, _) if guard => {
+                self.print_guarded(vars, printer, signed)
+            },
+            Div(l, r) if c_signed => c_signed_op("/", l, r, vars),
+            Mod(l, r) if c_signed => c_signed_op("%", l, r, vars),
+            Shr(l, r) if c_signed => c_signed_op(">>", l, r, vars),
+            Div(l, r) if python => masked_bin_op("//", l, r, vars),
+            Div(l, r) if js_number => {
+                format!("Math.floor({})", masked_bin_op("/", l, r, vars))
+            },
+            Div(l, r) if mask => masked_bin_op("/", l, r, vars),
+            Mod(l, r) if mask => masked_bin_op("%", l, r, vars),
+            // Signed division rounds towards zero.
+            Div(l, r) if tex && signed => {
+                format!("\\operatorname{{trunc}}\\left(\\frac{{{}}}{{{}}}\\right)",
+                    Self::print_simple_rc(l, vars, printer, signed),
+                    Self::print_simple_rc(r, vars, printer, signed))
+            },
+            Mod(l, r) if tex && signed => bin_op("\\operatorname{rem}", l, r, vars),
+            // The fraction already groups the operands.
+            Div(l, r) if tex => format!("\\left\\lfloor\\frac{{{}}}{{{}}}\\right\\rfloor",
+                Self::print_simple_rc(l, vars, printer, signed),
+                Self::print_simple_rc(r, vars, printer, signed)),
+            Mod(l, r) if tex => bin_op("\\bmod", l, r, vars),
             Div(l, r) => bin_op("/", l, r, vars),
             Mod(l, r) => bin_op("%", l, r, vars),
             Neg(i) => un_op("-", i, vars),
+            And(l, r) if tex => bin_op("\\land", l, r, vars),
+            Or(l, r) if tex => bin_op("\\lor", l, r, vars),
+            Xor(l, r) if tex => bin_op("\\oplus", l, r, vars),
+            Shl(l, r) if tex => bin_op("\\ll", l, r, vars),
+            Shr(l, r) if tex => bin_op("\\gg", l, r, vars),
             And(l, r) => bin_op("&", l, r, vars),
             Or(l, r) => bin_op("|", l, r, vars),
             Xor(l, r) => bin_op("^", l, r, vars),
+            Shl(l, r) if printer == Printer::Rust => rust_shift("<<", l, r, vars),
+            Shr(l, r) if printer == Printer::Rust => rust_shift(">>", l, r, vars),
+            Shl(l, r) if mask => masked_shift("<<", l, r, vars),
+            Shr(l, r) if js_number => masked_shift(">>>", l, r, vars),
+            Shr(l, r) if mask => masked_shift(">>", l, r, vars),
             Shl(l, r) => bin_op("<<", l, r, vars),
             Shr(l, r) => bin_op(">>", l, r, vars),
+            Not(i) if tex => {
+                let i = Self::print_simple_rc(i, vars, printer, signed);
+                format!("\\overline{{{}}}", i)
+            },
             Not(i) if printer == Printer::Rust => un_op("!", i, vars),
             Not(i) => un_op("~", i, vars),
         }
     }
-}
\ No newline at end of file
+
+    /// Prints a constant.
+    fn print_const(i: T, printer: Printer, signed: bool) -> String {
+        if signed {
+            let i = i.to_i128();
+            match printer {
+                Printer::Rust => format!("Wrapping({})", i),
+                Printer::C if c_is_bitint(T::bits()) => format!("{}uwb", i),
+                Printer::C if i64::try_from(i).is_err() => {
+                    format!("({}){}", c_type(T::bits(), true), c_const(i as u128, T::bits()))
+                },
+                // The literal in `-2147483648` doesn't fit into the signed type,
+                // so the minimum is written as a difference.
+                Printer::C if i == -1 << (T::bits() - 1) => format!("({} - 1)", i + 1),
+                Printer::JavaScript if js_bigint(T::bits()) => format!("{}n", i),
+                _ => format!("{}", i),
+            }
+        } else {
+            match printer {
+                Printer::Rust => format!("Wrapping({})", i),
+                Printer::C => c_const(i.to_u128(), T::bits()),
+                Printer::JavaScript if js_bigint(T::bits()) => format!("{}n", i),
+                _ => format!("{}", i),
+            }
+        }
+    }
+
+    /// Adds a variable for a value that isn't one of the subexpressions.
+    fn print_temp(vars: &mut Vec<(*const Self, String, String)>, init: String) -> String {
+        let v = format!("var_{}", vars.len());
+        vars.push((std::ptr::null(), v.clone(), init));
+        v
+    }
+
+    /// Prints a division, remainder or shift that needs a guard,
+    /// see [`Expr::needs_guard`], so it computes what [`Expr::eval`] does.
+    /// The cases that a constant right operand decides are left out.
+    fn print_guarded(
+        &self,
+        vars: &mut Vec<(*const Self, String, String)>,
+        printer: Printer,
+        signed: bool
+    ) -> String {
+        use Expr::*;
+        let (Div(l, r) | Mod(l, r) | Shl(l, r) | Shr(l, r)) = self else {
+            unreachable!("Only divisions, remainders and shifts need guards.")
+        };
+        let bits = T::bits();
+        let mask = printer.needs_mask(bits);
+        let c_signed = printer == Printer::C && signed;
+        let js_number = printer == Printer::JavaScript && !js_bigint(bits);
+        // The results can be constants, whose type Rust can't always infer.
+        let print_const = |i: T| match printer {
+            Printer::Rust if signed => format!("Wrapping({}{})", i.to_i128(), rust_int(bits, true)),
+            Printer::Rust => format!("Wrapping({}{})", i, rust_int(bits, false)),
+            _ => Self::print_const(i, printer, signed),
+        };
+        let (zero, one) = (print_const(T::zero()), print_const(T::one()));
+        let ones = print_const(T::zero() - T::one());
+
+        // The right operand is used by the guard and the operation
+        // and the left one by some of the results, so they are variables.
+        let rs = Self::print_var_rc(r, vars, printer, signed);
+        let ls = if matches!(self, Mod(_, _)) || signed && matches!(self, Div(_, _) | Shr(_, _)) {
+            Self::print_var_rc(l, vars, printer, signed)
+        } else if Rc::strong_count(l) == 1 && l.precedence() < 255 {
+            format!("({})", Self::print_simple_rc(l, vars, printer, signed))
+        } else {
+            Self::print_simple_rc(l, vars, printer, signed)
+        };
+
+        // Whether the right operand is 0, -1 and a valid shift amount,
+        // if it is a constant.
+        let (r_zero, r_minus_one, r_amount) = match r.as_ref() {
+            Const(c) => (
+                Some(c.is_zero()),
+                Some((T::zero() - *c).is_one()),
+                Some(shift_amount(*c).is_some()),
+            ),
+            _ => (None, None, None),
+        };
+
+        // The operands with the high bits cleared.
+        let unsigned = |s: &str| if mask {
+            format!("({})", Self::mask(s, 256, printer, false))
+        } else {
+            s.to_owned()
+        };
+        let (lu, ru) = (unsigned(&ls), unsigned(&rs));
+
+        // The operands as signed integers.
+        // The variables and constants already are signed integers.
+        let signed_operand = |e: &Rc<Self>, s: &str| match (printer, e.as_ref()) {
+            (Printer::C, Var(v)) => v.clone(),
+            (Printer::C, _) => format!("({}){}", c_type(bits, true), s),
+            (Printer::Python | Printer::JavaScript, Var(_) | Const(_)) => s.to_owned(),
+            _ if mask => format!("({})", Self::mask(s, 256, printer, true)),
+            _ => s.to_owned(),
+        };
+        let (mut sl, mut sr) = (signed_operand(l, &ls), signed_operand(r, &rs));
+
+        // Python's signed division uses the operands several times.
+        if printer == Printer::Python && signed && matches!(self, Div(_, _) | Mod(_, _)) {
+            for (e, s) in [(l, &mut sl), (r, &mut sr)] {
+                if !matches!(e.as_ref(), Var(_) | Const(_)) {
+                    *s = Self::print_temp(vars, s.clone());
+                }
+            }
+        }
+
+        // The shift amount is unsigned.
+        let amount = match printer {
+            Printer::Rust if mask || !signed => format!("{}.0", ru),
+            Printer::Rust => format!("({}.0 as u{})", rs, bits),
+            _ => ru.clone(),
+        };
+
+        // A number to compare with, which has to be a BigInt for BigInts.
+        let num = |i: u32| if printer == Printer::JavaScript && !js_number {
+            format!("{}n", i)
+        } else {
+            format!("{}", i)
+        };
+
+        let is_zero = match printer {
+            Printer::Rust => format!("{}.0 == 0", ru),
+            Printer::JavaScript => format!("{} === {}", ru, num(0)),
+            _ => format!("{} == 0", ru),
+        };
+        let in_range = format!("{} < {}", amount, num(bits));
+        let is_neg = |s: &str| match printer {
+            Printer::Rust => format!("{}.0 < 0", s),
+            _ => format!("{} < {}", s, num(0)),
+        };
+        let neg = |s: &str| if s.starts_with('-') {
+            format!("-({})", s)
+        } else {
+            format!("-{}", s)
+        };
+        let shift = |l: &str, op: &str| match printer {
+            Printer::Rust => format!("{} {} {} as usize", l, op, amount),
+            _ => format!("{} {} {}", l, op, amount),
+        };
+        // Python rounds the quotient down and the remainder has the sign of the divisor,
+        // so they are computed with the negated dividend if the signs differ.
+        let signed_op = |op: &str| match printer {
+            Printer::C => format!("({})({} {} {})", c_compute_type(bits), sl, op, sr),
+            Printer::Python => {
+                let op = if op == "/" { "//" } else { op };
+                format!("({sl} {op} {sr} if ({sl} ^ {sr}) >= 0 else -({} {op} {sr}))", neg(&sl))
+            },
+            Printer::JavaScript if js_number && op == "/" => format!("Math.trunc({} / {})", sl, sr),
+            _ => format!("{} {} {}", sl, op, sr),
+        };
+        let ternary = |c: &str, a: &str, b: &str| match printer {
+            Printer::Rust => format!("(if {} {{ {} }} else {{ {} }})", c, a, b),
+            Printer::Python => format!("({} if {} else {})", a, c, b),
+            _ => format!("({} ? {} : {})", c, a, b),
+        };
+
+        // The cases with their condition, whether a constant decides it
+        // and the result, and the result if none of them applies.
+        let (mut cases, default) = match self {
+            Div(_, _) if signed => {
+                let v = ternary(&is_neg(&sl), &one, &ones);
+                (vec![(is_zero, r_zero, v)], signed_op("/"))
+            },
+            Mod(_, _) if signed => (vec![(is_zero, r_zero, ls.clone())], signed_op("%")),
+            Div(_, _) => {
+                let div = match printer {
+                    Printer::Python => format!("{} // {}", lu, ru),
+                    Printer::JavaScript if js_number => format!("Math.floor({} / {})", lu, ru),
+                    _ => format!("{} / {}", lu, ru),
+                };
+                (vec![(is_zero, r_zero, ones.clone())], div)
+            },
+            Mod(_, _) => (vec![(is_zero, r_zero, ls.clone())], format!("{} % {}", lu, ru)),
+            Shl(_, _) => (vec![(in_range, r_amount, shift(&ls, "<<"))], zero.clone()),
+            // Python's right shifts already are defined for all shift amounts.
+            Shr(_, _) if printer == Printer::Python => {
+                let l = if signed { &sl } else { &lu };
+                (vec![], shift(l, ">>"))
+            },
+            Shr(_, _) if signed => {
+                let v = if c_signed {
+                    format!("({})({})", c_compute_type(bits), shift(&sl, ">>"))
+                } else {
+                    shift(&sl, ">>")
+                };
+                (vec![(in_range, r_amount, v)], ternary(&is_neg(&sl), &ones, &zero))
+            },
+            Shr(_, _) => {
+                let op = if js_number { ">>>" } else { ">>" };
+                (vec![(in_range, r_amount, shift(&lu, op))], zero.clone())
+            },
+            _ => unreachable!(),
+        };
+
+        // `INT_MIN / -1` and `INT_MIN % -1` overflow in C.
+        if c_signed && matches!(self, Div(_, _) | Mod(_, _)) {
+            let v = if matches!(self, Div(_, _)) { neg(&ls) } else { zero };
+            cases.push((format!("{} == -1", sr), r_minus_one, v));
+        }
+
+        cases.into_iter().rev().fold(default, |e, (cond, known, v)| match known {
+            Some(true) => v,
+            Some(false) => e,
+            None => ternary(&cond, &v, &e),
+        })
+    }
+
+    /// Prints the expression as an LLVM IR function.
+    /// Every operation is its own instruction
+    /// and shared subexpressions are only computed once.
+    /// The operands that would make divisions and shifts undefined or poison
+    /// are handled with `select`s, so the function computes what `eval` does.
+    fn print_llvm(&self, input: &[String], signed: bool) -> String {
+        let ty = format!("i{}", T::bits());
+        let mut insts = Vec::new();
+        let r = self.print_llvm_impl(&ty, signed, &mut insts, &mut Vec::new());
+
+        let args: Vec<_> = input.iter()
+            .map(|v| format!("{} %{}", ty, v))
+            .collect();
+        let mut s = format!("define {} @f({}) {{\n", ty, args.join(", "));
+        for i in &insts {
+            writeln!(&mut s, "\t{}", i);
+        }
+        write!(&mut s, "\tret {} {}\n}}", ty, r);
+        s
+    }
+
+    pub(crate) fn print_llvm_rc(
+        e: &Rc<Self>, ty: &str, signed: bool, insts: &mut Vec<String>,
+        vars: &mut Vec<(*const Self, String)>
+    ) -> String {
+        // If there is only one reference then just print it.
+        if Rc::strong_count(e) == 1 {
+            return e.print_llvm_impl(ty, signed, insts, vars);
+        }
+
+        // Otherwise reuse the value if it was already computed.
+        let ptr = Rc::as_ptr(e);
+        if let Some(v) = vars.iter().find(|t| t.0 == ptr) {
+            return v.1.clone();
+        }
+
+        let v = e.print_llvm_impl(ty, signed, insts, vars);
+        vars.push((ptr, v.clone()));
+        v
+    }
+
+    /// Appends the instructions that compute the expression
+    /// and returns the operand that holds the result.
+    /// The instructions are named `%t.N`, which can't be an input.
+    fn print_llvm_impl(
+        &self, ty: &str, signed: bool, insts: &mut Vec<String>,
+        vars: &mut Vec<(*const Self, String)>
+    ) -> String {
+        let mut rec = |e| Self::print_llvm_rc(e, ty, signed, insts, vars);
+
+        use Expr::*;
+        let (op, l, r) = match self {
+            Const(i) if i.print_negative() => return format!("-{}", T::zero() - *i),
+            Const(i) => return i.to_string(),
+            Var(v) => return format!("%{}", v),
+            Add(l, r) => ("add", rec(l), rec(r)),
+            Sub(l, r) => ("sub", rec(l), rec(r)),
+            Mul(l, r) => ("mul", rec(l), rec(r)),
+            Div(l, r) if signed => ("sdiv", rec(l), rec(r)),
+            Mod(l, r) if signed => ("srem", rec(l), rec(r)),
+            Div(l, r) => ("udiv", rec(l), rec(r)),
+            Mod(l, r) => ("urem", rec(l), rec(r)),
+            Neg(i) => ("sub", "0".to_owned(), rec(i)),
+            And(l, r) => ("and", rec(l), rec(r)),
+            Or(l, r) => ("or", rec(l), rec(r)),
+            Xor(l, r) => ("xor", rec(l), rec(r)),
+            Shl(l, r) => ("shl", rec(l), rec(r)),
+            Shr(l, r) if signed => ("ashr", rec(l), rec(r)),
+            Shr(l, r) => ("lshr", rec(l), rec(r)),
+            Not(i) => ("xor", rec(i), "-1".to_owned()),
+        };
+
+        let mut push = |inst: String| {
+            let v = format!("%t.{}", insts.len());
+            insts.push(format!("{} = {}", v, inst));
+            v
+        };
+
+        if !self.needs_guard(signed) {
+            return push(format!("{} {} {}, {}", op, ty, l, r));
+        }
+
+        // Division by zero and signed overflow are undefined behavior
+        // and shifting by the width or more is poison,
+        // so the result is selected like `eval` computes it.
+        // The instructions never see these operands,
+        // except for shifts, whose poison is discarded by the select.
+        match op {
+            "udiv" | "urem" => {
+                let z = push(format!("icmp eq {} {}, 0", ty, r));
+                let d = push(format!("select i1 {}, {} 1, {} {}", z, ty, ty, r));
+                let q = push(format!("{} {} {}, {}", op, ty, l, d));
+                let f = if op == "udiv" { "-1" } else { &l };
+                push(format!("select i1 {}, {} {}, {} {}", z, ty, f, ty, q))
+            },
+            "sdiv" | "srem" => {
+                let z = push(format!("icmp eq {} {}, 0", ty, r));
+                let m = push(format!("icmp eq {} {}, -1", ty, r));
+                let zm = push(format!("or i1 {}, {}", z, m));
+                let d = push(format!("select i1 {}, {} 1, {} {}", zm, ty, ty, r));
+                let q = push(format!("{} {} {}, {}", op, ty, l, d));
+                if op == "srem" {
+                    // `x % -1` is 0 like `x % 1`.
+                    return push(format!("select i1 {}, {} {}, {} {}", z, ty, l, ty, q));
+                }
+                let n = push(format!("sub {} 0, {}", ty, l));
+                let q = push(format!("select i1 {}, {} {}, {} {}", m, ty, n, ty, q));
+                let neg = push(format!("icmp slt {} {}, 0", ty, l));
+                let f = push(format!("select i1 {}, {} 1, {} -1", neg, ty, ty));
+                push(format!("select i1 {}, {} {}, {} {}", z, ty, f, ty, q))
+            },
+            _ => {
+                let c = push(format!("icmp ult {} {}, {}", ty, r, T::bits()));
+                let s = push(format!("{} {} {}, {}", op, ty, l, r));
+                let f = if op == "ashr" {
+                    push(format!("ashr {} {}, {}", ty, l, T::bits() - 1))
+                } else {
+                    "0".to_owned()
+                };
+                push(format!("select i1 {}, {} {}, {} {}", c, ty, s, ty, f))
+            },
+        }
+    }
+
+    /// Prints the expression as an SMT-LIB function
+    /// over bit vectors with the given name and arguments.
+    /// Shared subexpressions are bound with `let` to names like `t.0`,
+    /// which can't be the name of an input.
+    /// If `signed` is set, the signed operations are used.
+    pub fn print_smt_fn(&self, name: &str, args: &[String], signed: bool) -> String {
+        let ty = format!("(_ BitVec {})", T::bits());
+        let mut defs = Vec::new();
+        let body = self.print_smt_impl(signed, &mut defs, &mut Vec::new());
+
+        let args: Vec<_> = args.iter()
+            .map(|v| format!("({} {})", v, ty))
+            .collect();
+        let mut s = format!("(define-fun {} ({}) {}\n", name, args.join(" "), ty);
+        for (var, init) in &defs {
+            writeln!(&mut s, "\t(let (({} {}))", var, init);
+        }
+        write!(&mut s, "\t{}){}", body, ")".repeat(defs.len()));
+        s
+    }
+
+    fn print_smt_rc(
+        e: &Rc<Self>, signed: bool, defs: &mut Vec<(String, String)>,
+        vars: &mut Vec<(*const Self, String)>
+    ) -> String {
+        // If there is only one reference then just print it.
+        if Rc::strong_count(e) == 1 {
+            return e.print_smt_impl(signed, defs, vars);
+        }
+
+        // We don't want to assign a variable to a variable.
+        if let Expr::Var(v) = &**e {
+            return v.clone();
+        }
+
+        let ptr = Rc::as_ptr(e);
+        if let Some(v) = vars.iter().find(|t| t.0 == ptr) {
+            return v.1.clone();
+        }
+
+        // The variable is defined after the ones it uses.
+        // The dot keeps it apart from the inputs.
+        let init = e.print_smt_impl(signed, defs, vars);
+        let v = format!("t.{}", defs.len());
+        defs.push((v.clone(), init));
+        vars.push((ptr, v.clone()));
+        v
+    }
+
+    fn print_smt_impl(
+        &self, signed: bool, defs: &mut Vec<(String, String)>,
+        vars: &mut Vec<(*const Self, String)>
+    ) -> String {
+        let mut rec = |e| Self::print_smt_rc(e, signed, defs, vars);
+
+        use Expr::*;
+        let (op, l, r) = match self {
+            Const(i) => return format!("(_ bv{} {})", i, T::bits()),
+            Var(v) => return v.clone(),
+            Neg(i) => return format!("(bvneg {})", rec(i)),
+            Not(i) => return format!("(bvnot {})", rec(i)),
+            Add(l, r) => ("bvadd", rec(l), rec(r)),
+            Sub(l, r) => ("bvsub", rec(l), rec(r)),
+            Mul(l, r) => ("bvmul", rec(l), rec(r)),
+            Div(l, r) if signed => ("bvsdiv", rec(l), rec(r)),
+            Mod(l, r) if signed => ("bvsrem", rec(l), rec(r)),
+            Div(l, r) => ("bvudiv", rec(l), rec(r)),
+            Mod(l, r) => ("bvurem", rec(l), rec(r)),
+            And(l, r) => ("bvand", rec(l), rec(r)),
+            Or(l, r) => ("bvor", rec(l), rec(r)),
+            Xor(l, r) => ("bvxor", rec(l), rec(r)),
+            Shl(l, r) => ("bvshl", rec(l), rec(r)),
+            Shr(l, r) if signed => ("bvashr", rec(l), rec(r)),
+            Shr(l, r) => ("bvlshr", rec(l), rec(r)),
+        };
+
+        format!("({} {} {})", op, l, r)
+    }
+
+    /// Prints the expression with the high bits cleared,
+    /// or with the sign bit extended into them if `signed` is set.
+    fn mask_rc(
+        e: &Rc<Self>, vars: &mut Vec<(*const Self, String, String)>,
+        printer: Printer, signed: bool
+    ) -> String {
+        let s = Self::print_simple_rc(e, vars, printer, signed);
+        let pre = if Rc::strong_count(e) > 1 { 256 } else { e.precedence() };
+        format!("({})", Self::mask(&s, pre, printer, signed))
+    }
+
+    /// Clears the high bits of a printed expression
+    /// whose top-level operator has the given precedence.
+    /// The result is non-negative in all languages.
+    ///
+    /// If `signed` is set, the sign bit is extended into the high bits instead.
+    pub(crate) fn mask(s: &str, pre: usize, printer: Printer, signed: bool) -> String {
+        let m = mask(T::bits());
+        let (op, m, op_pre) = match printer {
+            Printer::Rust if signed => {
+                let k = rust_bits(T::bits()) - T::bits();
+                ("<<", format!("{} >> {}", k, k), 4)
+            },
+            Printer::Python if signed => {
+                let h = 1u128 << (T::bits() - 1);
+                let s = Self::mask(s, pre, printer, false);
+                return format!("({} ^ {:#x}) - {:#x}", s, h, h);
+            },
+            Printer::JavaScript if signed && js_bigint(T::bits()) => {
+                return format!("BigInt.asIntN({}, {})", T::bits(), s);
+            },
+            Printer::JavaScript if signed && T::bits() == 32 => ("|", "0".to_owned(), 1),
+            Printer::JavaScript if signed => {
+                let k = 32 - T::bits();
+                ("<<", format!("{} >> {}", k, k), 4)
+            },
+            Printer::Rust => ("&", format!("Wrapping({:#x})", m), 3),
+            // `& 0xffffffff` would result in a negative number in JavaScript.
+            Printer::JavaScript if T::bits() == 32 => (">>>", "0".to_owned(), 4),
+            Printer::JavaScript if js_bigint(T::bits()) => ("&", format!("{:#x}n", m), 3),
+            _ => ("&", format!("{:#x}", m), 3),
+        };
+
+        if pre >= op_pre {
+            format!("{} {} {}", s, op, m)
+        } else {
+            format!("({}) {} {}", s, op, m)
+        }
+    }
+
+    /// Evaluates the expression with the given values for the variables.
+    /// Panics if a variable has no value.
+    /// If `signed` is set, division, remainder and right shifts
+    /// interpret the numbers as two's complement integers.
+    ///
+    /// The operations that are usually undefined follow SMT-LIB:
+    /// `x / 0` is the number with all bits set, `x % 0` is `x`
+    /// and shifting by the width or more results in 0.
+    /// For signed integers, `x / 0` is 1 if `x` is negative,
+    /// and right shifts by the width or more fill the number with the sign bit.
+    /// The shift amount is always unsigned.
+    ///
+    /// Shared subexpressions are evaluated only once.
+    pub fn eval(&self, v: &Valuation<T>, signed: bool) -> T {
+        self.eval_impl(v, signed, &mut HashMap::new())
+    }
+
+    fn eval_rc(
+        e: &Rc<Self>, v: &Valuation<T>, signed: bool,
+        cache: &mut HashMap<*const Self, T>
+    ) -> T {
+        // Only shared nodes can be encountered twice.
+        if Rc::strong_count(e) == 1 {
+            return e.eval_impl(v, signed, cache);
+        }
+
+        let ptr = Rc::as_ptr(e);
+        if let Some(r) = cache.get(&ptr) {
+            return *r;
+        }
+
+        let r = e.eval_impl(v, signed, cache);
+        cache.insert(ptr, r);
+        r
+    }
+
+    fn eval_impl(
+        &self, v: &Valuation<T>, signed: bool,
+        cache: &mut HashMap<*const Self, T>
+    ) -> T {
+        use Expr::*;
+        let mut eval = |e| Self::eval_rc(e, v, signed, cache);
+        match self {
+            Const(c) => *c,
+            Var(name) => v[name],
+            Add(l, r) => eval(l) + eval(r),
+            Sub(l, r) => eval(l) - eval(r),
+            Mul(l, r) => eval(l) * eval(r),
+            Div(l, r) if signed => {
+                let (l, r) = (eval(l).to_i128(), eval(r).to_i128());
+                T::from_i128(match r {
+                    0 if l < 0 => 1,
+                    0 => -1,
+                    _ => l.wrapping_div(r),
+                })
+            },
+            Mod(l, r) if signed => {
+                let (l, r) = (eval(l).to_i128(), eval(r).to_i128());
+                if r == 0 { T::from_i128(l) } else { T::from_i128(l.wrapping_rem(r)) }
+            },
+            Div(l, r) => {
+                let (l, r) = (eval(l), eval(r));
+                if r.is_zero() { !T::zero() } else { l / r }
+            },
+            Mod(l, r) => {
+                let (l, r) = (eval(l), eval(r));
+                if r.is_zero() { l } else { l % r }
+            },
+            Neg(i) => T::zero() - eval(i),
+            And(l, r) => eval(l) & eval(r),
+            Or(l, r) => eval(l) | eval(r),
+            Xor(l, r) => eval(l) ^ eval(r),
+            Shl(l, r) => match shift_amount(eval(r)) {
+                Some(s) => eval(l) << s,
+                None => T::zero(),
+            },
+            Shr(l, r) if signed => {
+                let s = shift_amount(eval(r)).unwrap_or(127);
+                T::from_i128(eval(l).to_i128() >> s)
+            },
+            Shr(l, r) => match shift_amount(eval(r)) {
+                Some(s) => eval(l) >> s,
+                None => T::zero(),
+            },
+            Not(i) => !eval(i),
+        }
+    }
+}
+
+/// Sorts the arguments of a printed function.
+/// The auxiliary variables come last.
+pub(crate) fn sort_args(args: &mut [String]) {
+    args.sort_by(|l, r| {
+        if l.starts_with("aux") {
+            if r.starts_with("aux") {
+                l.cmp(r)
+            } else {
+                std::cmp::Ordering::Greater
+            }
+        } else if r.starts_with("aux") {
+            std::cmp::Ordering::Less
+        } else {
+            l.cmp(r)
+        }
+    });
+}
+
+/// Returns the shift amount if it is less than the width.
+fn shift_amount<T: UniformNum>(s: T) -> Option<usize> {
+    let s = s.to_u128();
+    (s < T::bits() as u128).then_some(s as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::Wrapping;
+    use super::*;
+
+    type W = Wrapping<u8>;
+
+    /// Evaluates the expression with the variables a, b and c set to the values.
+    fn eval(s: &str, vals: [u8; 3], signed: bool) -> u8 {
+        let e = Expr::<W>::from_string(s).unwrap();
+        let mut v = Valuation::zero(["a", "b", "c"].map(str::to_owned).to_vec());
+        for (n, x) in ["a", "b", "c"].iter().zip(vals) {
+            v[n] = Wrapping(x);
+        }
+        e.eval(&v, signed).0
+    }
+
+    #[test]
+    fn shift_precedence() {
+        use Expr::*;
+        let e = Expr::<W>::from_string("a + b << c").unwrap();
+        assert!(matches!(&e, Shl(l, r) if matches!(l.as_ref(), Add(_, _)) && matches!(r.as_ref(), Var(_))));
+        let e = Expr::<W>::from_string("a << b << c").unwrap();
+        assert!(matches!(&e, Shl(l, r) if matches!(l.as_ref(), Shl(_, _)) && matches!(r.as_ref(), Var(_))));
+        let e = Expr::<W>::from_string("a & b >> c").unwrap();
+        assert!(matches!(&e, And(_, r) if matches!(r.as_ref(), Shr(_, _))));
+
+        let v = [1, 2, 3];
+        assert_eq!(eval("a + b << c", v, false), 24);
+        assert_eq!(eval("a << b + c", v, false), 32);
+        assert_eq!(eval("a << b << c", v, false), 32);
+        assert_eq!(eval("a << (b << c)", v, false), 0);
+        assert_eq!(eval("a * 64 >> b >> c", v, false), 2);
+        assert_eq!(eval("a | b << c", v, false), 17);
+        assert_eq!(eval("c << a ^ b", v, false), 4);
+    }
+
+    /// Printing only keeps the parentheses that are needed.
+    #[test]
+    fn shift_printing() {
+        for (s, p) in [
+            ("(a + b) << c", "a + b << c"),
+            ("(a << b) << c", "a << b << c"),
+            ("a << (b << c)", "a << (b << c)"),
+            ("(a | b) << c", "(a | b) << c"),
+        ] {
+            let e = Expr::<W>::from_string(s).unwrap();
+            assert_eq!(e.print_as_fn(Printer::Default, false), p);
+        }
+    }
+
+    /// Division by zero and large shifts follow SMT-LIB.
+    #[test]
+    fn eval_unsigned() {
+        assert_eq!(eval("a / b", [7, 0, 0], false), 255);
+        assert_eq!(eval("a % b", [7, 0, 0], false), 7);
+        assert_eq!(eval("a / b", [249, 2, 0], false), 124);
+        assert_eq!(eval("a << b", [7, 8, 0], false), 0);
+        assert_eq!(eval("a >> b", [255, 8, 0], false), 0);
+        assert_eq!(eval("a << b", [7, 255, 0], false), 0);
+        assert_eq!(eval("a >> b", [128, 7, 0], false), 1);
+    }
+
+    #[test]
+    fn eval_signed() {
+        // -7 / 0 = 1, 7 / 0 = -1 and x % 0 = x.
+        assert_eq!(eval("a / b", [249, 0, 0], true), 1);
+        assert_eq!(eval("a / b", [7, 0, 0], true), 255);
+        assert_eq!(eval("a % b", [249, 0, 0], true), 249);
+
+        // Division rounds towards zero: -7 / 2 = -3 and -7 % 2 = -1.
+        assert_eq!(eval("a / b", [249, 2, 0], true), 253);
+        assert_eq!(eval("a % b", [249, 2, 0], true), 255);
+
+        // -128 / -1 overflows to -128.
+        assert_eq!(eval("a / b", [128, 255, 0], true), 128);
+        assert_eq!(eval("a % b", [128, 255, 0], true), 0);
+
+        // The shift amount is unsigned, so -1 is too large.
+        assert_eq!(eval("a >> b", [249, 8, 0], true), 255);
+        assert_eq!(eval("a >> b", [7, 255, 0], true), 0);
+        assert_eq!(eval("a >> b", [249, 255, 0], true), 255);
+        assert_eq!(eval("a >> b", [128, 7, 0], true), 255);
+        assert_eq!(eval("a << b", [255, 8, 0], true), 0);
+
+        let e = Expr::<Wrapping<u128>>::from_string("a / b").unwrap();
+        let mut v = Valuation::zero(vec!["a".to_owned(), "b".to_owned()]);
+        v["a"] = Wrapping(1 << 127);
+        v["b"] = Wrapping(u128::MAX);
+        assert_eq!(e.eval(&v, true), Wrapping(1 << 127));
+    }
+}
@@ -1,19 +1,50 @@
+//! Mixed Boolean-Arithmetic obfuscation.
+//!
+//! The core of the crate can be used natively.
+//! The bindings for the web pages are behind the `wasm` feature.
+
 #![allow(unused)]
 
-mod vector;
-mod matrix;
-mod numbers;
-mod polynomial;
-mod congruence_solver;
-mod expr;
-mod uniform_expr;
-mod printer;
+pub mod vector;
+pub mod matrix;
+pub mod numbers;
+pub mod polynomial;
+pub mod congruence_solver;
+pub mod expr;
+pub mod block;
+pub mod uniform_expr;
+pub mod printer;
+pub mod parser;
+pub mod perm_poly;
+pub mod verify;
+pub mod simplify;
+pub mod synthesis;
+pub mod obfuscate;
+pub mod opaque;
+
+#[cfg(feature = "wasm")]
 mod pages;
 
+pub use expr::Expr;
+pub use block::Block;
+pub use uniform_expr::{LUExpr, UExpr, Valuation};
+pub use congruence_solver::{AffineLattice, solve_congruences};
+pub use polynomial::Polynomial;
+pub use printer::Printer;
+pub use numbers::{UnsignedInt, UniformNum, UInt, Width};
+pub use parser::ParseError;
+pub use obfuscate::{ObfuscationConfig, ObfuscationError, SolutionChoice, obfuscate};
+
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+#[cfg(feature = "wasm")]
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
-    pub fn log(s: &str);
+    pub(crate) fn log(s: &str);
 }
+
+/// There is no console to log to outside of the browser.
+#[cfg(not(feature = "wasm"))]
+pub(crate) fn log(_s: &str) {}
@@ -3,7 +3,7 @@ use std::marker::PhantomData;
 use std::ops::{Index, IndexMut, Mul};
 use std::fmt::Write;
 use num_traits::{Num, NumAssign};
-use crate::numbers::UnsignedInt;
+use crate::numbers::{UnsignedInt, add_mod, mul_mod};
 use crate::vector::Vector;
 
 #[derive(Clone)]
@@ -78,12 +78,12 @@ impl<T> Matrix<T> {
     }
 
     /// Returns an iterator over the column.
-    pub fn col(&self, c: usize) -> Column<T> {
+    pub fn col(&self, c: usize) -> Column<'_, T> {
         Column::from_matrix(self, c)
     }
 
     /// Returns an iterator over mutable references to the elements in the column.
-    pub fn col_mut(&mut self, c: usize) -> ColumnMut<T> {
+    pub fn col_mut(&mut self, c: usize) -> ColumnMut<'_, T> {
         ColumnMut::from_matrix(self, c)
     }
 
@@ -206,6 +206,58 @@ impl<T: NumAssign + Copy> Matrix<T> {
 }
 
 impl<T: UnsignedInt> Matrix<T> {
+    /// Add a scaled row to another row modulo `modulus`. N = c * M.
+    /// The modulus 0 stands for the number of values of `T`.
+    pub fn row_multiply_add_mod(&mut self, n: usize, m: usize, c: T, modulus: T) {
+        for i in 0..self.cols {
+            let s = mul_mod(self[(n, i)], c, modulus);
+            self[(m, i)] = add_mod(self[(m, i)], s, modulus);
+        }
+    }
+
+    /// Add a scaled column to another column modulo `modulus`. N = c * M.
+    /// The modulus 0 stands for the number of values of `T`.
+    pub fn col_multiply_add_mod(&mut self, n: usize, m: usize, c: T, modulus: T) {
+        for i in 0..self.rows {
+            let s = mul_mod(self[(i, n)], c, modulus);
+            self[(i, m)] = add_mod(self[(i, m)], s, modulus);
+        }
+    }
+
+    /// Multiplies the matrices mod n.
+    /// The modulus `n = 0` stands for the number of values of `T`.
+    pub fn mul_mod(&self, rhs: &Self, n: T) -> Self {
+        debug_assert!(self.cols == rhs.rows,
+            "Can't multiply matrices because of incompatible dimensions");
+
+        let mut m = Self::zero(self.rows, rhs.cols);
+        for i in 0..m.rows {
+            for j in 0..m.cols {
+                m[(i, j)] = self.row(i).iter()
+                    .zip(rhs.col(j))
+                    .fold(T::zero(), |acc, (l, r)| add_mod(acc, mul_mod(*l, *r, n), n));
+            }
+        }
+
+        m
+    }
+
+    /// Multiplies the matrix by the vector mod n.
+    /// The modulus `n = 0` stands for the number of values of `T`.
+    pub fn mul_vec_mod(&self, rhs: &Vector<T>, n: T) -> Vector<T> {
+        debug_assert!(self.cols == rhs.dim,
+            "Can't multiply matrix/vector because of incompatible dimensions");
+
+        let mut m = Vector::<T>::zero(self.rows);
+        for i in 0..m.dim {
+            m[i] = self.row(i).iter()
+                .zip(rhs.iter())
+                .fold(T::zero(), |acc, (l, r)| add_mod(acc, mul_mod(*l, *r, n), n));
+        }
+
+        m
+    }
+
     /// Convert the matrix into a latex renderable string.
     pub fn to_tex(&self) -> String {
         let mut s = "\\left[\\begin{array}{}".to_owned();
@@ -2,16 +2,76 @@
 
 use std::ops::{
     IndexMut, Index, BitAnd, BitOr, BitXor, Not,
-    Shl, ShlAssign, Add, Sub, Mul, Div, Rem,
-    AddAssign, DivAssign, RemAssign, MulAssign, SubAssign
+    Shl, Shr, ShlAssign, Add, Sub, Mul, Div, Rem,
+    AddAssign, DivAssign, RemAssign, MulAssign, SubAssign,
+    BitAndAssign, BitOrAssign, BitXorAssign
 };
+use std::cell::Cell;
 use std::fmt::{self, Formatter, Display};
 use num_traits::{Num, NumAssign, Unsigned, Signed, Zero, One};
+use rand::Rng;
+use rand::distributions::{Distribution, Standard};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// The number of bits of the integers, between 1 and 128.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Width {
+    bits: u32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Width {
+    /// Returns an error if the number of bits is not between 1 and 128.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(bits: u32) -> Result<Width, String> {
+        if (1..=128).contains(&bits) {
+            Ok(Self { bits })
+        } else {
+            Err(format!("The width has to be between 1 and 128 bits, not {}.", bits))
+        }
+    }
+
+    /// The number of bits.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+}
+
+impl Width {
+    pub const U8: Self = Self { bits: 8 };
+    pub const U16: Self = Self { bits: 16 };
+    pub const U32: Self = Self { bits: 32 };
+    pub const U64: Self = Self { bits: 64 };
+    pub const U128: Self = Self { bits: 128 };
+}
+
+/// Calls the generic function `$f` with the integer type for the width,
+/// i.e. `with_width!(w, f(a, b))` is `f::<Wrapping<u8>>(a, b)` for 8 bits.
+/// The widths without a primitive type use [`UInt`] with the width set
+/// for the duration of the call.
+macro_rules! with_width {
+    ($w:expr, $f:ident $args:tt) => {
+        match $w.bits() {
+            8 => $f::<std::num::Wrapping<u8>> $args,
+            16 => $f::<std::num::Wrapping<u16>> $args,
+            32 => $f::<std::num::Wrapping<u32>> $args,
+            64 => $f::<std::num::Wrapping<u64>> $args,
+            128 => $f::<std::num::Wrapping<u128>> $args,
+            bits => $crate::numbers::UInt::with_width(bits, || $f::<$crate::numbers::UInt> $args),
+        }
+    };
+}
+
+pub(crate) use with_width;
 
 /// The integers mod n.
 /// Representatives in the range 0..n are stored.
 pub trait UnsignedInt: NumAssign + Copy + Ord + Unsigned + Display {
-    /// Should the number be printed as a negative number.
+    /// Should the number be printed as a negative number,
+    /// i.e. is it negative when interpreted as a signed integer.
     fn print_negative(self) -> bool {
         false
     }
@@ -20,40 +80,104 @@ pub trait UnsignedInt: NumAssign + Copy + Ord + Unsigned + Display {
     fn from_u8(v: u8) -> Self;
 }
 
-/// Parses an integer in base ten from the iterator.
-pub(crate) fn int_from_it<T: UnsignedInt>(
-    it: &mut std::iter::Peekable<std::str::Chars>
-) -> Option<T> {
-    let neg = *it.peek()? == '-';
-    if neg {
-        it.next();
+/// Returns `a + b mod n` for `a, b < n`.
+/// The modulus `n = 0` stands for the number of values of `T`,
+/// i.e. the addition wraps around as usual.
+pub(crate) fn add_mod<T: UnsignedInt>(a: T, b: T, n: T) -> T {
+    if n.is_zero() || a < n - b {
+        a + b
+    } else {
+        a - (n - b)
+    }
+}
+
+/// Returns `-a mod n` for `a < n`, see [`add_mod`].
+pub(crate) fn neg_mod<T: UnsignedInt>(a: T, n: T) -> T {
+    if a.is_zero() {
+        a
+    } else if n.is_zero() {
+        T::zero() - a
+    } else {
+        n - a
+    }
+}
+
+/// Returns `a * b mod n` for `a, b < n`, see [`add_mod`].
+pub(crate) fn mul_mod<T: UnsignedInt>(a: T, b: T, n: T) -> T {
+    if n.is_zero() {
+        return a * b;
     }
 
-    // Is the character an ascii digit?
-    if !it.peek().map_or(false, |c| c.is_ascii_digit()) {
-        return None;
+    // Double and add, because the product doesn't necessarily fit into T.
+    let two = T::one() + T::one();
+    let (mut r, mut a, mut b) = (T::zero(), a, b);
+    while !b.is_zero() {
+        if b % two == T::one() {
+            r = add_mod(r, a, n);
+        }
+        a = add_mod(a, a, n);
+        b /= two;
     }
+    r
+}
+
+/// Parses an integer literal.
+/// Hexadecimal, octal and binary literals start with `0x`, `0o` and `0b`.
+/// The digits can be separated by `_` or `'` and the literal can end
+/// in a Rust suffix like `u8` or `i32`, or a C suffix like `u` or `ull`.
+///
+/// If the literal is invalid or doesn't fit into the width of `T`
+/// or the range of its suffix, a description of what was expected is returned.
+/// Literals are never negative, so a signed suffix like `i8`
+/// allows up to 128, which is needed for `-128i8`.
+pub(crate) fn int_from_str<T: UniformNum>(s: &str) -> Result<T, &'static str> {
+    let (radix, digits, expected) = match s.get(..2) {
+        Some("0x" | "0X") => (16, &s[2..], "hexadecimal digit"),
+        Some("0o" | "0O") => (8, &s[2..], "octal digit"),
+        Some("0b" | "0B") => (2, &s[2..], "binary digit"),
+        _ => (10, s, "digit"),
+    };
+
+    let end = digits.find(|c: char| !c.is_digit(radix) && c != '_' && c != '\'')
+        .unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(end);
 
-    let ten = T::from_u8(10);
+    let suffix_bits = match suffix.to_ascii_lowercase().as_str() {
+        "" | "u" | "l" | "ul" | "lu" | "ll" | "ull" | "llu" => 128,
+        "u8" | "i8" => 8,
+        "u16" | "i16" => 16,
+        "u32" | "i32" => 32,
+        "u64" | "i64" => 64,
+        "u128" | "i128" => 128,
+        _ if digits.is_empty() => return Err(expected),
+        _ => return Err("integer suffix"),
+    };
 
     // Parse the number.
-    let mut n = T::zero();
-    loop {
-        // Is this still a digit?
-        let Some(d) = it.peek().and_then(|c| c.to_digit(10)) else {
-            break
-        };
+    let mut n = 0u128;
+    let mut any = false;
+    for c in digits.chars().filter_map(|c| c.to_digit(radix)) {
+        n = n.checked_mul(radix as u128)
+            .and_then(|n| n.checked_add(c as u128))
+            .ok_or("constant that fits into the width")?;
+        any = true;
+    }
+
+    if !any {
+        return Err(expected);
+    }
 
-        n *= ten;
-        n += T::from_u8(d as u8);
-        it.next();
+    let bits = T::bits().min(suffix_bits);
+    if bits < 128 && n >> bits != 0 {
+        return Err("constant that fits into the width");
     }
 
-    if neg {
-        n = T::zero() - n;        
+    let signed = suffix.starts_with(['i', 'I']);
+    if signed && n > 1 << (suffix_bits - 1) {
+        return Err("constant that fits into the signed type");
     }
 
-    Some(n)
+    Ok(T::from_u128(n))
 }
 
 /// N-bit integers basically.
@@ -61,21 +185,227 @@ pub trait UniformNum: UnsignedInt
     + BitAnd<Self, Output = Self>
     + BitOr<Self, Output = Self>
     + BitXor<Self, Output = Self>
-    + Shl<usize>
+    + Shl<usize, Output = Self>
+    + Shr<usize, Output = Self>
     + ShlAssign<usize>
-    + Not<Output = Self> {}
+    + Not<Output = Self> {
+    /// The number of bits.
+    fn bits() -> u32;
+
+    /// Converts the number to a u128.
+    fn to_u128(self) -> u128;
+
+    /// Converts a u128 to the number, discarding the high bits.
+    fn from_u128(v: u128) -> Self;
+
+    /// The value when the number is interpreted as a two's complement integer.
+    fn to_i128(self) -> i128 {
+        let s = 128 - Self::bits();
+        ((self.to_u128() << s) as i128) >> s
+    }
+
+    /// Converts an i128 to the number, discarding the high bits.
+    fn from_i128(v: i128) -> Self {
+        Self::from_u128(v as u128)
+    }
+}
+
+macro_rules! impl_uniform {
+    ($impl_ty:ty) => {
+        impl UniformNum for std::num::Wrapping<$impl_ty> {
+            fn bits() -> u32 {
+                <$impl_ty>::BITS
+            }
+
+            fn to_u128(self) -> u128 {
+                self.0 as u128
+            }
+
+            fn from_u128(v: u128) -> Self {
+                std::num::Wrapping(v as $impl_ty)
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// The width of [`UInt`] on this thread.
+    static UINT_BITS: Cell<u32> = const { Cell::new(128) };
+}
+
+/// An unsigned integer with a number of bits between 1 and 128
+/// that is set at runtime with [`UInt::with_width`].
+/// All operations are mod 2^N, like for `Wrapping`.
+/// Shifting by N or more bits results in 0.
+///
+/// The width is per thread and numbers must not be mixed across widths.
+/// The primitive widths should use `Wrapping` instead, which is faster.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UInt(u128);
+
+impl UInt {
+    /// Calls `f` with the width set to `bits`.
+    /// The previous width is restored afterwards, even if `f` panics.
+    pub fn with_width<R>(bits: u32, f: impl FnOnce() -> R) -> R {
+        assert!((1..=128).contains(&bits), "Widths are between 1 and 128 bits.");
+
+        struct Restore(u32);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                UINT_BITS.with(|b| b.set(self.0));
+            }
+        }
+
+        let _restore = Restore(UINT_BITS.with(|b| b.replace(bits)));
+        f()
+    }
+
+    /// The bits that are used.
+    pub fn mask() -> u128 {
+        u128::MAX >> (128 - Self::bits())
+    }
+
+    /// Creates the number, discarding the high bits.
+    pub fn new(v: u128) -> Self {
+        Self(v & Self::mask())
+    }
+
+    /// The value in the range 0..2^N.
+    pub const fn value(self) -> u128 {
+        self.0
+    }
+}
+
+impl Display for UInt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
 
-impl UniformNum for std::num::Wrapping<u8> {}
-impl UniformNum for std::num::Wrapping<u16> {}
-impl UniformNum for std::num::Wrapping<u32> {}
-impl UniformNum for std::num::Wrapping<u64> {}
-impl UniformNum for std::num::Wrapping<u128> {}
+macro_rules! impl_uint_op {
+    ($trait:ident, $fn:ident, $assign_trait:ident, $assign_fn:ident, $op:expr) => {
+        impl $trait for UInt {
+            type Output = Self;
+
+            fn $fn(self, rhs: Self) -> Self {
+                Self::new($op(self.0, rhs.0))
+            }
+        }
+
+        impl $assign_trait for UInt {
+            fn $assign_fn(&mut self, rhs: Self) {
+                *self = $trait::$fn(*self, rhs);
+            }
+        }
+    }
+}
+
+impl_uint_op!(Add, add, AddAssign, add_assign, u128::wrapping_add);
+impl_uint_op!(Sub, sub, SubAssign, sub_assign, u128::wrapping_sub);
+impl_uint_op!(Mul, mul, MulAssign, mul_assign, u128::wrapping_mul);
+impl_uint_op!(Div, div, DivAssign, div_assign, u128::div);
+impl_uint_op!(Rem, rem, RemAssign, rem_assign, u128::rem);
+impl_uint_op!(BitAnd, bitand, BitAndAssign, bitand_assign, u128::bitand);
+impl_uint_op!(BitOr, bitor, BitOrAssign, bitor_assign, u128::bitor);
+impl_uint_op!(BitXor, bitxor, BitXorAssign, bitxor_assign, u128::bitxor);
+
+impl Not for UInt {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::new(!self.0)
+    }
+}
+
+impl Shl<usize> for UInt {
+    type Output = Self;
+
+    fn shl(self, rhs: usize) -> Self {
+        if rhs < Self::bits() as usize { Self::new(self.0 << rhs) } else { Self(0) }
+    }
+}
+
+impl ShlAssign<usize> for UInt {
+    fn shl_assign(&mut self, rhs: usize) {
+        *self = *self << rhs;
+    }
+}
+
+impl Shr<usize> for UInt {
+    type Output = Self;
+
+    fn shr(self, rhs: usize) -> Self {
+        if rhs < Self::bits() as usize { Self(self.0 >> rhs) } else { Self(0) }
+    }
+}
+
+impl Zero for UInt {
+    fn zero() -> Self {
+        Self(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl One for UInt {
+    fn one() -> Self {
+        Self::new(1)
+    }
+}
+
+impl Num for UInt {
+    type FromStrRadixErr = std::num::ParseIntError;
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        u128::from_str_radix(s, radix).map(Self::new)
+    }
+}
+
+impl Unsigned for UInt {}
+
+impl UnsignedInt for UInt {
+    fn print_negative(self) -> bool {
+        self.0 >> (Self::bits() - 1) != 0
+    }
+
+    fn from_u8(v: u8) -> Self {
+        Self::new(v as u128)
+    }
+}
+
+impl UniformNum for UInt {
+    fn bits() -> u32 {
+        UINT_BITS.with(Cell::get)
+    }
+
+    fn to_u128(self) -> u128 {
+        self.0
+    }
+
+    fn from_u128(v: u128) -> Self {
+        Self::new(v)
+    }
+}
+
+impl Distribution<UInt> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> UInt {
+        UInt::new(rng.gen())
+    }
+}
+
+impl_uniform!(u8);
+impl_uniform!(u16);
+impl_uniform!(u32);
+impl_uniform!(u64);
+impl_uniform!(u128);
 
 macro_rules! impl_uint {
     ($impl_ty:ty) => {
         impl UnsignedInt for std::num::Wrapping<$impl_ty> {
             fn print_negative(self) -> bool {
-                self.0 > (1 << (<$impl_ty>::BITS - 1))
+                self.0 >= (1 << (<$impl_ty>::BITS - 1))
             }
 
             fn from_u8(v: u8) -> Self {
@@ -90,4 +420,63 @@ impl_uint!(u16);
 impl_uint!(u32);
 impl_uint!(u64);
 impl_uint!(u128);
-impl_uint!(usize);
\ No newline at end of file
+impl_uint!(usize);
+#[cfg(test)]
+mod tests {
+    use std::num::Wrapping;
+    use super::*;
+
+    type W8 = Wrapping<u8>;
+    type W64 = Wrapping<u64>;
+
+    #[test]
+    fn int_prefixes() {
+        assert_eq!(int_from_str::<W64>("1234"), Ok(Wrapping(1234)));
+        assert_eq!(int_from_str::<W64>("0x1aF"), Ok(Wrapping(0x1af)));
+        assert_eq!(int_from_str::<W64>("0X10"), Ok(Wrapping(16)));
+        assert_eq!(int_from_str::<W64>("0o17"), Ok(Wrapping(15)));
+        assert_eq!(int_from_str::<W64>("0b1011"), Ok(Wrapping(11)));
+        assert_eq!(int_from_str::<W64>("0x"), Err("hexadecimal digit"));
+        assert_eq!(int_from_str::<W64>("0o8"), Err("octal digit"));
+        assert_eq!(int_from_str::<W64>("0b2"), Err("binary digit"));
+    }
+
+    #[test]
+    fn int_separators() {
+        assert_eq!(int_from_str::<W64>("1_000_000"), Ok(Wrapping(1_000_000)));
+        assert_eq!(int_from_str::<W64>("1'000'000"), Ok(Wrapping(1_000_000)));
+        assert_eq!(int_from_str::<W64>("0xff_ff"), Ok(Wrapping(0xffff)));
+        assert_eq!(int_from_str::<W64>("0b_1"), Ok(Wrapping(1)));
+        assert_eq!(int_from_str::<W64>("0x_"), Err("hexadecimal digit"));
+    }
+
+    #[test]
+    fn int_suffixes() {
+        assert_eq!(int_from_str::<W64>("10u"), Ok(Wrapping(10)));
+        assert_eq!(int_from_str::<W64>("10ULL"), Ok(Wrapping(10)));
+        assert_eq!(int_from_str::<W64>("10lu"), Ok(Wrapping(10)));
+        assert_eq!(int_from_str::<W64>("255u8"), Ok(Wrapping(255)));
+        assert_eq!(int_from_str::<W64>("0xffu16"), Ok(Wrapping(255)));
+        assert_eq!(int_from_str::<W64>("127i8"), Ok(Wrapping(127)));
+        assert_eq!(int_from_str::<W64>("128i8"), Ok(Wrapping(128)));
+        assert_eq!(int_from_str::<W64>("10x"), Err("integer suffix"));
+        assert_eq!(int_from_str::<W64>("10u7"), Err("integer suffix"));
+    }
+
+    #[test]
+    fn int_overflow() {
+        assert_eq!(int_from_str::<W8>("255"), Ok(Wrapping(255)));
+        assert_eq!(int_from_str::<W8>("256"), Err("constant that fits into the width"));
+        assert_eq!(int_from_str::<W64>("256u8"), Err("constant that fits into the width"));
+        assert_eq!(int_from_str::<W64>("200i8"), Err("constant that fits into the signed type"));
+        assert_eq!(int_from_str::<W64>("32769i16"), Err("constant that fits into the signed type"));
+        assert_eq!(int_from_str::<W64>("0x8000_0001i32"), Err("constant that fits into the signed type"));
+        assert_eq!(int_from_str::<Wrapping<u128>>(&u128::MAX.to_string()), Ok(Wrapping(u128::MAX)));
+        assert_eq!(int_from_str::<Wrapping<u128>>("340282366920938463463374607431768211456"),
+            Err("constant that fits into the width"));
+        UInt::with_width(3, || {
+            assert_eq!(int_from_str::<UInt>("7"), Ok(UInt::new(7)));
+            assert_eq!(int_from_str::<UInt>("8"), Err("constant that fits into the width"));
+        });
+    }
+}
@@ -0,0 +1,769 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter, Write};
+use std::rc::Rc;
+use rand::{Rng, SeedableRng};
+use rand::distributions::{Standard, Distribution};
+use rand_chacha::ChaCha8Rng;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use crate::congruence_solver::solve_congruences;
+use crate::matrix::Matrix;
+use crate::vector::Vector;
+use crate::printer::Printer;
+use crate::expr::Expr;
+use crate::block::Block;
+use crate::uniform_expr::{LUExpr, UExpr, Valuation};
+use crate::numbers::{UnsignedInt, UniformNum, Width, with_width};
+use crate::parser::ParseError;
+use crate::verify::{Counterexample, verify};
+use crate::simplify::signature;
+use crate::synthesis::{TruthTable, synthesize};
+use crate::perm_poly::{ZeroIdeal, rand_perm_poly, invert_newton};
+
+/// An error that prevents obfuscation.
+#[derive(Debug)]
+pub enum ObfuscationError {
+    /// The input expression could not be parsed.
+    Parse(ParseError),
+
+    /// The result is not equivalent to the input.
+    /// This is a bug in the obfuscator.
+    Mismatch {
+        /// The values of the variables for which the results differ.
+        valuation: Vec<(String, u128)>,
+
+        /// The value of the input.
+        expected: u128,
+
+        /// The value of the obfuscated expression.
+        found: u128,
+    },
+
+    /// Any other problem with the input.
+    Other(String),
+}
+
+impl Display for ObfuscationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ObfuscationError::Parse(e) => write!(f, "{}", e),
+            ObfuscationError::Mismatch { valuation, expected, found } => {
+                f.write_str("The obfuscated expression is wrong for ")?;
+                for (i, (var, val)) in valuation.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{} = {}", var, val)?;
+                }
+                write!(f, ": expected {}, found {}", expected, found)
+            },
+            ObfuscationError::Other(s) => f.write_str(s),
+        }
+    }
+}
+
+impl std::error::Error for ObfuscationError {}
+
+impl From<ParseError> for ObfuscationError {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl<T: UniformNum> From<Counterexample<T>> for ObfuscationError {
+    fn from(c: Counterexample<T>) -> Self {
+        Self::Mismatch {
+            valuation: c.valuation.iter()
+                .map(|(var, val)| (var.clone(), val.to_u128()))
+                .collect(),
+            expected: c.expected.to_u128(),
+            found: c.found.to_u128(),
+        }
+    }
+}
+
+impl From<String> for ObfuscationError {
+    fn from(s: String) -> Self {
+        Self::Other(s)
+    }
+}
+
+impl From<&str> for ObfuscationError {
+    fn from(s: &str) -> Self {
+        Self::Other(s.to_owned())
+    }
+}
+
+/// How [`rewrite`] picks one of the solutions,
+/// i.e. the coefficients of the operations.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolutionChoice {
+    /// The particular solution that the solver returns.
+    Particular,
+
+    /// A random solution.
+    Random,
+
+    /// A solution with small coefficients as signed integers,
+    /// which is easier to read.
+    Short,
+
+    /// A solution with few non-zero coefficients,
+    /// i.e. few operations are used.
+    Sparse,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug)]
+pub struct ObfuscationConfig {
+    /// The expression to obfuscate.
+    /// This can also be a block of assignments with several results,
+    /// e.g. `a = x + y; b = a ^ z; return a * b, b`, see [`Block`].
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
+    pub expr: String,
+
+    /// The integer width.
+    pub width: Width,
+
+    /// How to print the result.
+    pub printer: Printer,
+
+    /// The number of auxiliary variables to use.
+    pub aux_vars: usize,
+
+    /// The depth of the random expressions whose truth tables
+    /// are used for the rewrite expressions.
+    pub rewrite_depth: u8,
+
+    /// The number of rewrite expressions to use.
+    pub rewrite_count: usize,
+
+    /// Wrap the linear MBA expressions in a random permutation polynomial
+    /// and its inverse, which makes the result non-linear.
+    /// Linear MBA expressions can be simplified, see [`simplify`],
+    /// so this is needed to really hide constants.
+    ///
+    /// [`simplify`]: crate::simplify::simplify
+    pub perm_poly: bool,
+
+    /// How the coefficients of the rewrite operations are chosen.
+    pub solution: SolutionChoice,
+
+    /// Interpret the integers as signed, i.e. division, remainder
+    /// and right shifts are signed and constants are printed as signed numbers.
+    pub signed: bool,
+
+    /// The seed for the random number generator.
+    /// The same seed and input always produce the same output.
+    /// If there is no seed, a random one is used.
+    pub seed: Option<u64>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ObfuscationConfig {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        Self {
+            expr: String::new(),
+            width: Width::U8,
+            printer: Printer::C,
+            aux_vars: 0,
+            rewrite_depth: 3,
+            rewrite_count: 24,
+            perm_poly: false,
+            solution: SolutionChoice::Random,
+            signed: false,
+            seed: None,
+        }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_expr(&mut self, expr: String) {
+        self.expr = expr;
+    }
+}
+
+impl Default for ObfuscationConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Obfuscates the expression in the config
+/// and prints the result as a function.
+pub fn obfuscate(cfg: &ObfuscationConfig) -> Result<String, ObfuscationError> {
+    with_width!(cfg.width, obfuscate_impl(cfg))
+}
+
+fn obfuscate_impl<T: UniformNum + std::fmt::Debug>(
+    cfg: &ObfuscationConfig
+) -> Result<String, ObfuscationError>
+    where Standard: Distribution<T>
+{
+    // The obfuscation modifies the expressions in place,
+    // so the input that the result is checked against is parsed separately.
+    let input = Block::<T>::from_string(&cfg.expr)?;
+    let mut block = Block::<T>::from_string(&cfg.expr)?;
+
+    let mut vars = block.vars();
+    for i in 0..cfg.aux_vars {
+        vars.push(format!("aux{}", i));
+    }
+
+    // Standalone constants report the missing variables themselves.
+    let constants = block.outputs.iter().all(|e| matches!(**e, Expr::Const(_)));
+    if vars.is_empty() && !constants {
+        return Err("No variables to obfuscate with. Add auxiliary variables.".into());
+    }
+
+    // The outputs share the variables and the visited subexpressions,
+    // so subexpressions shared between them are only obfuscated once.
+    // The zero ideal is only needed for the permutation polynomials.
+    let mut rng = rng_from_seed(cfg.seed);
+    let mut v = Vec::new();
+    let zi = cfg.perm_poly.then(ZeroIdeal::init);
+    for e in &mut block.outputs {
+        // A standalone constant is hidden in the auxiliary variables.
+        if let Expr::Const(c) = **e {
+            *e = Rc::new(obfuscate_constant_impl(c, &vars, cfg, zi.as_ref(), &mut rng)?);
+        } else {
+            obfuscate_expr(e, &mut v, &vars, cfg, zi.as_ref(), &mut rng)?;
+        }
+    }
+
+    for (i, o) in input.outputs.iter().zip(&block.outputs) {
+        verify(i, o, &vars, cfg.signed, &mut rng)?;
+    }
+
+    Ok(block.print_as_fn(cfg.printer, cfg.signed))
+}
+
+/// Tries to convert the expression to a uniform expression.
+/// When part of the expression isn't a uniform expression,
+/// it generates a variable and remembers what expression to
+/// substitute for that variable.
+fn expr_to_uexpr<T: UniformNum>(
+    e: &Rc<Expr<T>>, subs: &mut Vec<(String, Rc<Expr<T>>)>
+) -> UExpr {
+    if Rc::strong_count(e) > 1 {
+        let var = format!("_sub_{}", subs.len());
+        subs.push((var.clone(), e.clone()));
+        return UExpr::Var(var);
+    }
+
+    match e.as_ref() {
+        Expr::Var(v) => UExpr::Var(v.clone()),
+        Expr::And(l, r) => UExpr::and(expr_to_uexpr(l, subs), expr_to_uexpr(r, subs)),
+        Expr::Or(l, r) => UExpr::or(expr_to_uexpr(l, subs), expr_to_uexpr(r, subs)),
+        Expr::Xor(l, r) => UExpr::xor(expr_to_uexpr(l, subs), expr_to_uexpr(r, subs)),
+        Expr::Not(i) => UExpr::not(expr_to_uexpr(i, subs)),
+        // Otherwise generate a new variable and add the substitution.
+        _ => {
+            let var = format!("_sub_{}", subs.len());
+            subs.push((var.clone(), e.clone()));
+            UExpr::Var(var)
+        }
+    }
+}
+
+/// Tries to convert an expression
+fn parse_term<T: UniformNum>(
+    e: &Rc<Expr<T>>, subs: &mut Vec<(String, Rc<Expr<T>>)>
+) -> (T, UExpr) {
+    if let Expr::Mul(l, r) = e.as_ref() {
+        if let Expr::Const(i) = l.as_ref() {
+            return (*i, expr_to_uexpr(r, subs));
+        } else if let Expr::Const(i) = r.as_ref() {
+            return (*i, expr_to_uexpr(l, subs));
+        }
+    } else if let Expr::Const(c) = e.as_ref() {
+        return (T::zero() - *c, UExpr::Ones);
+    }
+
+    (T::one(), expr_to_uexpr(e, subs))
+}
+
+fn expr_to_luexpr<T: UniformNum>(
+    e: &Rc<Expr<T>>,
+    lu: &mut LUExpr<T>,
+    subs: &mut Vec<(String, Rc<Expr<T>>)>,
+    sign: bool
+) {
+    // If this is an add the left and right hand side
+    // can contribute to the linear combination.
+    match e.as_ref() {
+        Expr::Add(l, r) => {
+            expr_to_luexpr(l, lu, subs, sign);
+            expr_to_luexpr(r, lu, subs, sign);
+        },
+
+        Expr::Sub(l, r) => {
+            expr_to_luexpr(l, lu, subs, sign);
+            expr_to_luexpr(r, lu, subs, !sign);
+        },
+
+        Expr::Neg(i) => {
+            // Theoretically we could allow another whole
+            // LUExpr in here but hopefully not too important.
+
+            // Flipped because of the Neg.
+            let f = if sign { T::one() } else { T::zero() - T::one() };
+            lu.0.push((f, expr_to_uexpr(i, subs)));
+        },
+
+        // Otherwise parse the term from this expression.
+        _ => {
+            let (mut f, u) = parse_term(e, subs);
+            if sign {
+                f = T::zero() - f;
+            }
+            lu.0.push((f, u));
+        },
+    }
+}
+
+/// Obfuscates the expression in place.
+/// The linear MBA subexpressions are wrapped in permutation polynomials
+/// if the zero ideal is given.
+fn obfuscate_expr<T: UniformNum, R: Rng>(
+    er: &mut Rc<Expr<T>>,
+    visited: &mut Vec<*const Expr<T>>,
+    vars: &[String],
+    cfg: &ObfuscationConfig,
+    zi: Option<&ZeroIdeal<T>>,
+    rng: &mut R,
+) -> Result<(), ObfuscationError>
+    where Standard: Distribution<T>
+{
+    let ptr = Rc::as_ptr(er);
+    if Rc::strong_count(er) > 1 {
+        if visited.contains(&ptr) {
+            return Ok(());
+        }
+        visited.push(ptr);
+    }
+
+    let e = unsafe { &mut *(ptr as *mut _) };
+
+    match e {
+        Expr::Mul(l, r) => {
+            obfuscate_expr(l, visited, vars, cfg, zi, rng)?;
+            obfuscate_expr(r, visited, vars, cfg, zi, rng)?;
+        },
+        Expr::Div(l, r) | Expr::Mod(l, r) => {
+            obfuscate_expr(l, visited, vars, cfg, zi, rng)?;
+            obfuscate_expr(r, visited, vars, cfg, zi, rng)?;
+        },
+        Expr::Shl(l, r) | Expr::Shr(l, r) => {
+            obfuscate_expr(l, visited, vars, cfg, zi, rng)?;
+            obfuscate_expr(r, visited, vars, cfg, zi, rng)?;
+        },
+        _ => {
+            // Try to find the largest subexpression that is linear MBA
+            // and obfuscate it on its own.
+            let mut lu = LUExpr(Vec::new());
+
+            // Substitutions in the LUExpr.
+            let mut subs: Vec<(String, Rc<Expr<T>>)> = Vec::new();
+
+            // The expression itself can be shared, e.g. by several outputs,
+            // but it must not become a substitution of itself.
+            // Moving it into a new Rc makes it unshared.
+            let root = Rc::new(std::mem::replace(e, Expr::zero()));
+            expr_to_luexpr(&root, &mut lu, &mut subs, false);
+            drop(root);
+            *e = rewrite_random(&lu, vars, cfg, rng)?.to_expr();
+            if let Some(zi) = zi {
+                *e = wrap_perm_poly(std::mem::replace(e, Expr::zero()), zi, rng);
+            }
+            for (var, sub) in &mut subs {
+                // Obfuscate the substituted expressions.
+                obfuscate_expr(sub, visited, vars, cfg, zi, rng)?;
+
+                // Substitute them for the variables.
+                e.substitute(sub, var);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `q(p(e))` for a random permutation polynomial `p`
+/// and its inverse `q`, which is an expression equivalent to `e`.
+fn wrap_perm_poly<T: UniformNum, R: Rng>(
+    e: Expr<T>, zi: &ZeroIdeal<T>, rng: &mut R
+) -> Expr<T>
+    where Standard: Distribution<T>
+{
+    let p = rand_perm_poly(zi, rng);
+    let q = invert_newton(&p, zi);
+    let pe = p.to_expr(Rc::new(e));
+    q.to_expr(Rc::new(pe))
+}
+
+/// Returns an expression in the variables `vars` that always evaluates to `c`.
+/// This hides constants like keys or magic numbers.
+/// The variables can be anything whose value is unknown to an attacker,
+/// e.g. arguments of the surrounding function or values loaded from memory.
+///
+/// If `perm_poly` is set in the config, the result is `q(e)`
+/// for a random permutation polynomial `p` with inverse `q`,
+/// where `e` is a linear MBA expression for `p(c)`.
+/// Otherwise the result is a linear MBA expression,
+/// which [`simplify`] turns back into `c`,
+/// so only the permutation polynomial really hides the constant.
+/// Only the rewrite and perm_poly settings of the config are used.
+///
+/// [`simplify`]: crate::simplify::simplify
+pub fn obfuscate_constant<T: UniformNum, R: Rng>(
+    c: T, vars: &[String], cfg: &ObfuscationConfig, rng: &mut R
+) -> Result<Expr<T>, ObfuscationError>
+    where Standard: Distribution<T>
+{
+    let zi = cfg.perm_poly.then(ZeroIdeal::init);
+    obfuscate_constant_impl(c, vars, cfg, zi.as_ref(), rng)
+}
+
+/// See [`obfuscate_constant`].
+/// The permutation polynomial is only used if the zero ideal is given.
+fn obfuscate_constant_impl<T: UniformNum, R: Rng>(
+    c: T, vars: &[String], cfg: &ObfuscationConfig, zi: Option<&ZeroIdeal<T>>, rng: &mut R
+) -> Result<Expr<T>, ObfuscationError>
+    where Standard: Distribution<T>
+{
+    if vars.is_empty() {
+        return Err("No variables to hide the constant in. Add auxiliary variables.".into());
+    }
+
+    // The constant that the linear MBA expression evaluates to.
+    let (q, d) = if let Some(zi) = zi {
+        let p = rand_perm_poly(zi, rng);
+        let q = invert_newton(&p, zi);
+        (Some(q), p.eval(c))
+    } else {
+        (None, c)
+    };
+
+    // The constant d is -d * Ones.
+    let lu = LUExpr(vec![(T::zero() - d, UExpr::Ones)]);
+    let mut e = rewrite_random(&lu, vars, cfg, rng)?.to_expr();
+    if let Some(q) = q {
+        e = q.to_expr(Rc::new(e));
+    }
+
+    verify(&Expr::Const(c), &e, vars, cfg.signed, rng)?;
+    Ok(e)
+}
+
+const REWRITE_TRIES: usize = 128;
+
+/// Rewrites the expression as a linear combination of random operations
+/// in the variables, see [`rewrite`].
+/// Returns an error if no random operations could express it,
+/// which happens with too few or too shallow operations.
+pub(crate) fn rewrite_random<T: UniformNum, R: Rng>(
+    e: &LUExpr<T>, vars: &[String], cfg: &ObfuscationConfig, rng: &mut R
+) -> Result<LUExpr<T>, ObfuscationError>
+    where Standard: Distribution<T>
+{
+    let mut vars: Vec<_> = vars.to_vec();
+    for v in e.vars() {
+        if !vars.contains(&v) {
+            vars.push(v);
+        }
+    }
+    for _ in 0..REWRITE_TRIES {
+        // Duplicate operations would just be merged by `rewrite`,
+        // so we only use distinct ones. With few variables there
+        // might not be enough distinct ones, so we give up eventually.
+        let mut ops = Vec::new();
+        for _ in 0..4 * cfg.rewrite_count {
+            if ops.len() == cfg.rewrite_count {
+                break;
+            }
+
+            if let Some(op) = random_op(&vars, cfg.rewrite_depth, rng) {
+                if !ops.contains(&op) {
+                    ops.push(op);
+                }
+            }
+        }
+
+        let ops: Vec<_> = ops.into_iter().map(LUExpr::from_uexpr).collect();
+        if let Some(r) = rewrite(e, &ops, cfg.solution, rng) {
+            return Ok(r);
+        }
+    }
+
+    Err(format!("Failed to rewrite the expression with {} operations of depth {}. \
+        Use more or deeper rewrite operations.", cfg.rewrite_count, cfg.rewrite_depth).into())
+}
+
+/// Returns the smallest expression we can find for the function
+/// computed by a random expression of the given depth.
+/// Returns None if the function is zero, as that is useless for rewriting.
+pub(crate) fn random_op<R: Rng>(vars: &[String], max_depth: u8, rng: &mut R) -> Option<UExpr> {
+    let e = random_bool_expr(vars, max_depth, rng);
+    let vars = e.vars();
+    let t = TruthTable::of_uexpr(&e, &vars);
+    (!t.is_false()).then(|| synthesize(&t, &vars))
+}
+
+/// Note that this never generates `Ones` or any expression containing it,
+/// as those can be easily simplified to one that does not contain it.
+fn random_bool_expr<T: AsRef<str>, R: Rng>(
+    vars: &[T], max_depth: u8, rng: &mut R
+) -> UExpr {
+    assert!(!vars.is_empty(), "There needs to be at least one variable for the random expression.");
+
+    // Sample u32s rather than usizes, because the latter
+    // would give different results on 32-bit (wasm) and 64-bit platforms.
+    let mut rand_var = |rng: &mut R| UExpr::Var(
+        vars[rng.gen_range(0..vars.len() as u32) as usize].as_ref().to_owned()
+    );
+
+    if max_depth == 0 {
+        return rand_var(rng);
+    }
+
+    // Generate one of the four variants uniformly at random.
+    let d = max_depth - 1;
+    match rng.gen_range(0u32..5) {
+        0 => rand_var(rng),
+        1 => UExpr::Not(random_bool_expr(vars, d, rng).into()),
+        2 => UExpr::And(random_bool_expr(vars, d, rng).into(), random_bool_expr(vars, d, rng).into()),
+        3 => UExpr::Or(random_bool_expr(vars, d, rng).into(), random_bool_expr(vars, d, rng).into()),
+        4 => UExpr::Xor(random_bool_expr(vars, d, rng).into(), random_bool_expr(vars, d, rng).into()),
+        _ => panic!("If you get here, mathematics is broken."),
+    }
+}
+
+/// Creates the random number generator used for obfuscation.
+/// ChaCha8 is used instead of `StdRng`, because its output is guaranteed
+/// to stay the same across versions of `rand` and platforms.
+/// Without a seed, the generator is seeded from the operating system.
+pub fn rng_from_seed(seed: Option<u64>) -> ChaCha8Rng {
+    match seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    }
+}
+
+/// Rewrites the linear combination of uniform expressions in the request
+/// using the operations in the request and prints the result.
+pub fn obfuscate_linear(req: ObfLinReq) -> Result<String, ObfuscationError> {
+    with_width!(req.bits, obfuscate_linear_impl(req))
+}
+
+fn obfuscate_linear_impl<T>(
+    req: ObfLinReq
+) -> Result<String, ObfuscationError>
+    where
+        T: UniformNum + std::fmt::Display,
+        Standard: Distribution<T>
+{
+    let expr = LUExpr::<T>::from_string(&req.expr)?;
+
+    let ops = req.ops.iter()
+        .map(|s| LUExpr::<T>::from_string(s).map_err(|e|
+            format!("Failed to parse operation '{}': {}", s, e)
+        ))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut rng = rng_from_seed(req.seed);
+    let res = rewrite(&expr, &ops, req.solution, &mut rng)
+        .ok_or("Operations can't be used to rewrite the input")?;
+
+    let mut vars = BTreeSet::new();
+    expr.vars_impl(&mut vars);
+    res.vars_impl(&mut vars);
+    let vars: Vec<_> = vars.into_iter().collect();
+    verify(&expr.to_expr(), &res.to_expr(), &vars, req.signed, &mut rng)?;
+
+    Ok(req.printer.print_luexpr(&res, req.signed))
+}
+
+/// Rewrites `expr` as a linear combination of the operations `ops`.
+/// The solution is picked as specified by `solution`,
+/// where `rng` is used for random solutions.
+/// Returns None if this isn't possible.
+pub fn rewrite<T, R>(
+    expr: &LUExpr<T>, ops: &[LUExpr<T>], solution: SolutionChoice, rng: &mut R
+) -> Option<LUExpr<T>>
+    where
+        T: UniformNum + std::fmt::Display,
+        R: Rng,
+        Standard: Distribution<T>
+{
+    // Without operations, the system would be empty.
+    if ops.is_empty() {
+        return None;
+    }
+
+    // Find all variables.
+    let mut v = BTreeSet::new();
+    expr.vars_impl(&mut v);
+    for op in ops {
+        op.vars_impl(&mut v);
+    }
+
+    let v: Vec<_> = v.into_iter().collect();
+
+    // The columns of the matrix are the signatures of the operations.
+    let mut a = Matrix::zero(1 << v.len(), ops.len());
+    for (j, op) in ops.iter().enumerate() {
+        let s = signature(&v, |val| op.eval(val));
+        for (i, e) in s.iter().enumerate() {
+            a[(i, j)] = *e;
+        }
+    }
+
+    // The desired result.
+    let b = signature(&v, |val| expr.eval(val));
+
+    // Solve the system.
+    let l = solve_congruences(a, &b);
+
+    // Does it have solutions?
+    if l.is_empty() {
+        return None;
+    }
+
+    // Pick a point from the lattice.
+    let solution = match solution {
+        SolutionChoice::Particular => l.offset,
+        SolutionChoice::Random => l.sample(rng),
+        SolutionChoice::Short => l.small_point(false),
+        SolutionChoice::Sparse => l.small_point(true),
+    };
+
+    // Put it in an LUExpr.
+    // Currently, this simplifies the inner LUExprs into
+    // sums of UExprs, such that the result is an LUExpr.
+    // Once there is a more general Expr class, we need not do this.
+    let mut v = Vec::new();
+    for (c, o) in solution.iter().zip(ops.iter()) {
+        for (d, e) in &o.0 {
+            // Is the UExpr already in the linear combination?
+            match v.iter_mut().find(|(_, f)| f == e) {
+                Some((f, _)) => *f += *c * *d,
+                None => v.push((*c * *d, e.clone())),
+            }
+        }
+    }
+
+    // Remove terms where the coefficient is zero.
+    v.retain(|(f, u)| !f.is_zero());
+
+    Some(LUExpr(v))
+}
+
+/// Obfuscation settings.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug)]
+pub struct ObfLinReq {
+    /// The expression to obfuscate.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
+    pub expr: String,
+
+    /// The operations used for rewriting.
+    /// There is currently an issue with this because we verify the ops
+    /// with a certain bitness but the obfuscation may happen with another one.
+    /// This is only really a problem with big constants though, so not that
+    /// likely to happen to anyone.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
+    pub ops: Vec<String>,
+
+    /// The integer width.
+    pub bits: Width,
+
+    /// How the solution is picked.
+    pub solution: SolutionChoice,
+
+    /// How to print the result.
+    pub printer: Printer,
+
+    /// Print a function on signed integers.
+    /// Linear MBA computes the same for signed and unsigned integers,
+    /// so this only changes how the result is printed.
+    pub signed: bool,
+
+    /// The seed for the random number generator.
+    /// If there is no seed, a random one is used.
+    pub seed: Option<u64>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ObfLinReq {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        Self {
+            expr: String::new(),
+            ops: Vec::new(),
+            bits: Width::U8,
+            solution: SolutionChoice::Random,
+            printer: Printer::C,
+            signed: false,
+            seed: None,
+        }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_expr(&mut self, expr: String) {
+        self.expr = expr;
+    }
+
+    pub fn add_op(&mut self, op: String) {
+        self.ops.push(op);
+    }
+}
+
+impl Default for ObfLinReq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn too_few_operations() {
+        let mut cfg = ObfuscationConfig::new();
+        cfg.expr = "x + y".to_owned();
+        cfg.rewrite_count = 1;
+        cfg.rewrite_depth = 0;
+        cfg.seed = Some(0);
+        assert!(matches!(obfuscate(&cfg), Err(ObfuscationError::Other(_))));
+
+        cfg.rewrite_count = 0;
+        assert!(matches!(obfuscate(&cfg), Err(ObfuscationError::Other(_))));
+
+        // This used to panic for some seeds.
+        cfg.expr = "var0 * y + var1".to_owned();
+        cfg.rewrite_count = 2;
+        cfg.rewrite_depth = 1;
+        for seed in 0..32 {
+            cfg.seed = Some(seed);
+            let _ = obfuscate(&cfg);
+        }
+    }
+
+    #[test]
+    fn standalone_constant() {
+        let mut cfg = ObfuscationConfig::new();
+        cfg.expr = "123".to_owned();
+        cfg.seed = Some(0);
+        match obfuscate(&cfg) {
+            Err(ObfuscationError::Other(e)) => assert!(e.contains("constant"), "{}", e),
+            r => panic!("Expected the error for constants, got {:?}", r),
+        }
+
+        cfg.aux_vars = 2;
+        cfg.perm_poly = true;
+        assert!(obfuscate(&cfg).is_ok());
+    }
+}
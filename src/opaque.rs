@@ -0,0 +1,78 @@
+//! Opaque predicates, i.e. conditions that are always true or always false,
+//! but are hard to prove so.
+
+use std::rc::Rc;
+use rand::Rng;
+use rand::distributions::{Standard, Distribution};
+use crate::expr::Expr;
+use crate::uniform_expr::{LUExpr, UExpr};
+use crate::numbers::UniformNum;
+use crate::obfuscate::{ObfuscationConfig, ObfuscationError, rewrite_random, random_op};
+use crate::perm_poly::{ZeroIdeal, rand_null_poly};
+use crate::verify::verify;
+
+/// Returns an expression in the variables `vars` that evaluates to `c` for all inputs.
+///
+/// The expression is `z(e) + l`, where `e` is a linear MBA expression
+/// for a random uniform expression, `z` is a random null polynomial
+/// and `l` is a linear MBA expression for `c`.
+/// The null polynomial makes the result non-linear,
+/// so it can't be simplified with the linear MBA algorithms.
+///
+/// Comparing the result to `c` is an always true predicate
+/// and comparing it to anything else an always false one,
+/// see [`Expr::print_as_condition`].
+/// Only the rewrite settings of the config are used.
+pub fn opaque_constant<T: UniformNum, R: Rng>(
+    c: T, vars: &[String], cfg: &ObfuscationConfig, rng: &mut R
+) -> Result<Expr<T>, ObfuscationError>
+    where Standard: Distribution<T>
+{
+    if vars.is_empty() {
+        return Err("Opaque predicates need at least one variable.".into());
+    }
+
+    // The argument of the null polynomial.
+    let u = loop {
+        if let Some(u) = random_op(vars, cfg.rewrite_depth, rng) {
+            break u;
+        }
+    };
+    let e = rewrite_random(&LUExpr::from_uexpr(u), vars, cfg, rng)?.to_expr();
+
+    let zi = ZeroIdeal::init();
+    let z = rand_null_poly(&zi, rng).to_expr(Rc::new(e));
+
+    // The constant c is -c * Ones.
+    let l = LUExpr(vec![(T::zero() - c, UExpr::Ones)]);
+    let l = rewrite_random(&l, vars, cfg, rng)?.to_expr();
+
+    let e = Expr::Add(Rc::new(z), Rc::new(l));
+    verify(&Expr::Const(c), &e, vars, cfg.signed, rng)?;
+    Ok(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use crate::numbers::{Width, with_width};
+    use super::*;
+
+    /// Checks that an opaque constant is generated, which also verifies it.
+    fn check_opaque_constant<T: UniformNum>() where Standard: Distribution<T> {
+        let mut rng = ChaCha8Rng::seed_from_u64(T::bits() as u64);
+        let vars = ["x".to_owned(), "y".to_owned()];
+        let c = rng.gen();
+        if let Err(e) = opaque_constant(c, &vars, &ObfuscationConfig::new(), &mut rng) {
+            panic!("{} bits: {}", T::bits(), e);
+        }
+    }
+
+    #[test]
+    fn opaque_constant_all_widths() {
+        for bits in 1..=128 {
+            with_width!(Width::new(bits).unwrap(), check_opaque_constant())
+        }
+    }
+}
@@ -1,13 +1,13 @@
 use wasm_bindgen::prelude::*;
-use std::fmt::Display;
-use std::num::Wrapping;
+use num_traits::Zero;
 use crate::vector::Vector;
 use crate::matrix::Matrix;
-use super::{Width, bold, underbrace};
+use super::{bold, underbrace};
+use std::num::Wrapping;
+use crate::numbers::{UniformNum, Width, int_from_str, with_width};
 use crate::congruence_solver::{
-    AffineLattice, diagonalize, solve_scalar_congruence
+    AffineLattice, diagonalize_mod, smith_normal_form, solve_scalar_congruence_mod
 };
-use crate::numbers::UnsignedInt;
 
 /// Stores the intermediate results during the computation of the solution.
 #[wasm_bindgen]
@@ -26,6 +26,9 @@ pub struct SolveTrace {
 
     /// The final solution.
     final_solution: String,
+
+    /// The number of solutions if the modulus is a power of two.
+    solution_count: String,
 }
 
 #[wasm_bindgen]
@@ -54,20 +57,41 @@ impl SolveTrace {
     pub fn final_solution(&self) -> String {
         self.final_solution.clone()
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn solution_count(&self) -> String {
+        self.solution_count.clone()
+    }
 }
 
-fn solve_congruences_impl<T: UnsignedInt + Display>(
-    a: Matrix<&str>, b: Vector<&str>
+/// Solves the system mod n, where `n = 0` stands for the number of values of `T`.
+fn solve_congruences_impl<T: UniformNum>(
+    a: Matrix<&str>, b: Vector<&str>, n: T
 ) -> Result<SolveTrace, String> {
-    let a = a.try_map(|&e| T::from_str_radix(e, 10))
+    // The entries are reduced mod n.
+    let parse = |e: &str| T::from_str_radix(e, 10)
+        .map(|e| if n.is_zero() { e } else { e % n });
+
+    let a = a.try_map(|&e| parse(e))
         .map_err(|(r, c, _)| format!("Failed to parse entry ({}, {}).", r+1, c+1))?;
 
-    let b = b.try_map(|&e| T::from_str_radix(e, 10))
+    let b = b.try_map(|&e| parse(e))
         .map_err(|(r, _)| format!("Failed to parse entry ({}, {}).", r+1, a.cols+1))?;
     
-    let mut d = a.clone();
-    let (s, t) = diagonalize(&mut d);
-    assert!(&(&s * &a) * &t == d);
+    // For powers of two, the Smith normal form is unique.
+    let (d, s, t, solution_count) = if n.is_zero() {
+        let snf = smith_normal_form(&a);
+        let count = match snf.solution_count_log2(&b) {
+            Some(k) => format!("2^{{{}}}", k),
+            None => String::new(),
+        };
+        (snf.d, snf.s, snf.t, count)
+    } else {
+        let mut d = a.clone();
+        let (s, t) = diagonalize_mod(&mut d, n);
+        (d, s, t, String::new())
+    };
+    assert!(s.mul_mod(&a, n).mul_mod(&t, n) == d);
 
     let diag = format!("{}={}{}{}",
         underbrace(d.to_tex(), bold("D")),
@@ -78,7 +102,7 @@ fn solve_congruences_impl<T: UnsignedInt + Display>(
 
     // Transform the vector b.
     // We could already do this in diagonalize if we really wanted.
-    let b_new = (&s * &b);
+    let b_new = s.mul_vec_mod(&b, n);
 
     let scalar_system = format!("{}\\mathbf{{x'}}={}{}={}",
         underbrace(d.to_tex(), bold("D")),
@@ -104,7 +128,8 @@ fn solve_congruences_impl<T: UnsignedInt + Display>(
             scalar_system,
             linear_solutions,
             vector_solution: String::new(),
-            final_solution: String::new()
+            final_solution: String::new(),
+            solution_count,
         });
     }
 
@@ -123,7 +148,7 @@ fn solve_congruences_impl<T: UnsignedInt + Display>(
     for i in 0..d.min_dim() {
         linear_solutions += &format!(
             "{}x'_{{{}}}&={} &\\implies ", d[(i, i)], i + 1, b[i]);
-        let (x, kern) = match solve_scalar_congruence(d[(i, i)], b[i]) {
+        let (x, kern) = match solve_scalar_congruence_mod(d[(i, i)], b[i], n) {
             // If there is no solution,
             // then the whole system does not have a solution.
             None => {
@@ -134,6 +159,7 @@ fn solve_congruences_impl<T: UnsignedInt + Display>(
                     linear_solutions,
                     vector_solution: String::new(),
                     final_solution: String::new(),
+                    solution_count,
                 });
             },
             Some(s) => s,
@@ -179,14 +205,15 @@ fn solve_congruences_impl<T: UnsignedInt + Display>(
     let mut solution = AffineLattice {
         offset,
         basis,
+        modulus: n,
     };
 
     let x_old = solution.to_tex_brace();
     let vector_solution = format!("\\mathbf{{x'}}={}", x_old);
 
-    solution.offset = (&t * &solution.offset);
+    solution.offset = t.mul_vec_mod(&solution.offset, n);
     for v in &mut solution.basis {
-        *v = (&t * &*v);
+        *v = t.mul_vec_mod(v, n);
     }
 
     let final_solution = format!("\\mathbf{{x}}={}{}={}",
@@ -201,11 +228,14 @@ fn solve_congruences_impl<T: UnsignedInt + Display>(
         linear_solutions,
         vector_solution,
         final_solution,
+        solution_count,
     })
 }
 
+/// Solves the system given by the augmented matrix mod the modulus.
+/// The modulus is either a power of two like `2^8` or any positive integer.
 #[wasm_bindgen]
-pub fn solve_congruences(matrix_str: String, bit: Width) -> Result<SolveTrace, String> {
+pub fn solve_congruences(matrix_str: String, modulus: String) -> Result<SolveTrace, String> {
     // The number of rows is the number of lines.
     let rows = matrix_str.lines().count();
     if rows == 0 {
@@ -246,11 +276,20 @@ pub fn solve_congruences(matrix_str: String, bit: Width) -> Result<SolveTrace, S
         }
     }
 
-    match bit {
-        Width::U8 => solve_congruences_impl::<Wrapping<u8>>(a, b),
-        Width::U16 => solve_congruences_impl::<Wrapping<u16>>(a, b),
-        Width::U32 => solve_congruences_impl::<Wrapping<u32>>(a, b),
-        Width::U64 => solve_congruences_impl::<Wrapping<u64>>(a, b),
-        Width::U128 => solve_congruences_impl::<Wrapping<u128>>(a, b),
+    // Powers of two use the integers of that width, whose wrap-around is the modulus.
+    // Other moduli can be up to 128 bits.
+    let modulus = modulus.trim();
+    if let Some(bits) = modulus.strip_prefix("2^") {
+        let bits = bits.trim().parse()
+            .map_err(|_| format!("Invalid exponent '{}'.", bits))?;
+        let w = Width::new(bits)?;
+        with_width!(w, solve_congruences_impl(a, b, Zero::zero()))
+    } else {
+        let n = int_from_str::<Wrapping<u128>>(modulus)
+            .map_err(|e| format!("Invalid modulus '{}': Expected {}.", modulus, e))?;
+        if n.is_zero() {
+            return Err("The modulus has to be positive.".into());
+        }
+        solve_congruences_impl(a, b, n)
     }
 }
\ No newline at end of file
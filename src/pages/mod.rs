@@ -5,15 +5,92 @@ mod linear_congruences;
 mod perm_poly;
 
 use wasm_bindgen::prelude::*;
+use crate::parser::ParseError;
+use crate::obfuscate::ObfuscationError;
 
+/// An error caused by the user input.
+/// If the input could not be parsed, then the error
+/// has a location that the page can highlight.
 #[wasm_bindgen]
-#[derive(Clone, Copy, Debug)]
-pub enum Width {
-    U8,
-    U16,
-    U32,
-    U64,
-    U128,
+#[derive(Debug)]
+pub struct InputError {
+    /// The error message.
+    message: String,
+
+    /// The start and end of the error in UTF-16 code units,
+    /// which is how javascript indexes strings.
+    span: Option<(usize, usize)>,
+
+    /// Descriptions of the tokens that would have been valid.
+    expected: Vec<String>,
+
+    /// Description of the offending token.
+    found: Option<String>,
+}
+
+#[wasm_bindgen]
+impl InputError {
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn start(&self) -> Option<usize> {
+        self.span.map(|s| s.0)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn end(&self) -> Option<usize> {
+        self.span.map(|s| s.1)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn expected(&self) -> Vec<String> {
+        self.expected.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn found(&self) -> Option<String> {
+        self.found.clone()
+    }
+}
+
+impl InputError {
+    /// Converts a parse error of `input`.
+    pub fn parse(input: &str, e: ParseError) -> Self {
+        // Convert the byte offsets to UTF-16 offsets.
+        let utf16 = |i: usize| input[..i].encode_utf16().count();
+        Self {
+            message: e.to_string(),
+            span: Some((utf16(e.span.start), utf16(e.span.end))),
+            expected: e.expected.iter().map(|s| s.to_string()).collect(),
+            found: Some(e.found),
+        }
+    }
+}
+
+impl InputError {
+    /// Converts an error that occurred while obfuscating `input`.
+    pub fn obfuscation(input: &str, e: ObfuscationError) -> Self {
+        match e {
+            ObfuscationError::Parse(e) => Self::parse(input, e),
+            e @ ObfuscationError::Mismatch { .. } => e.to_string().into(),
+            ObfuscationError::Other(s) => s.into(),
+        }
+    }
+}
+
+impl From<String> for InputError {
+    fn from(message: String) -> Self {
+        Self { message, span: None, expected: Vec::new(), found: None }
+    }
+}
+
+impl From<&str> for InputError {
+    fn from(message: &str) -> Self {
+        message.to_owned().into()
+    }
 }
 
 pub fn underbrace<T: AsRef<str>, U: AsRef<str>>(inner: T, label: U) -> String {
@@ -22,4 +99,22 @@ pub fn underbrace<T: AsRef<str>, U: AsRef<str>>(inner: T, label: U) -> String {
 
 pub fn bold<T: AsRef<str>>(inner: T) -> String {
     format!("\\mathbf{{{}}}", inner.as_ref())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::Wrapping;
+    use crate::expr::Expr;
+    use super::*;
+
+    /// The span is converted from bytes to UTF-16 code units.
+    #[test]
+    fn utf16_span() {
+        let s = "𝔁 + €";
+        let e = Expr::<Wrapping<u8>>::from_string(s).unwrap_err();
+        assert_eq!((e.span.start, e.span.end), (7, 10));
+        let e = InputError::parse(s, e);
+        assert_eq!((e.start(), e.end()), (Some(5), Some(6)));
+        assert_eq!(e.found(), Some("'€'".to_owned()));
+    }
+}
@@ -0,0 +1,251 @@
+//! Tokenizer and error type shared by the expression parsers.
+
+use std::fmt::{self, Display, Formatter};
+
+/// Byte offsets of a token in the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// Offset of the first byte.
+    pub start: usize,
+
+    /// Offset one past the last byte.
+    pub end: usize,
+}
+
+/// The tokens of the expression languages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// An integer literal.
+    Num(&'a str),
+
+    /// A variable name.
+    Ident(&'a str),
+
+    /// An operator or a bracket.
+    Sym(&'static str),
+
+    /// A character that is not part of any token.
+    Unknown(char),
+
+    /// The end of the input.
+    Eof,
+}
+
+impl<'a> Display for Token<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Num(n) => write!(f, "'{}'", n),
+            Token::Ident(v) => write!(f, "'{}'", v),
+            Token::Sym(s) => write!(f, "'{}'", s),
+            Token::Unknown(c) => write!(f, "'{}'", c),
+            Token::Eof => f.write_str("end of input"),
+        }
+    }
+}
+
+/// All symbols, two character symbols first,
+/// so that they take precedence over their prefixes.
+const SYMBOLS: &[&str] = &[
+    "<<", ">>",
+    "(", ")", "+", "-", "*", "/", "%", "&", "|", "^", "~", "!",
+    "=", ";", ",",
+];
+
+/// Splits the input into tokens.
+/// Whitespace is skipped, but the byte offsets of the tokens
+/// always refer to the original input.
+pub struct Lexer<'a> {
+    /// The input.
+    src: &'a str,
+
+    /// Offset of the first byte that has not been tokenized.
+    pos: usize,
+
+    /// The next token, if it has already been peeked.
+    peeked: Option<(Token<'a>, Span)>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self { src, pos: 0, peeked: None }
+    }
+
+    /// Returns the next token without consuming it.
+    pub fn peek(&mut self) -> (Token<'a>, Span) {
+        match self.peeked {
+            Some(t) => t,
+            None => {
+                let t = self.lex();
+                self.peeked = Some(t);
+                t
+            }
+        }
+    }
+
+    /// Consumes the next token.
+    pub fn next_token(&mut self) -> (Token<'a>, Span) {
+        match self.peeked.take() {
+            Some(t) => t,
+            None => self.lex(),
+        }
+    }
+
+    /// Consumes the next token if it is the symbol `sym`.
+    /// Otherwise returns an error where `expected` describes
+    /// all tokens that would have been valid.
+    pub fn expect(
+        &mut self, sym: &'static str, expected: &[&'static str]
+    ) -> Result<(), ParseError> {
+        match self.next_token() {
+            (Token::Sym(s), _) if s == sym => Ok(()),
+            (t, span) => Err(ParseError::new(t, span, expected)),
+        }
+    }
+
+    /// Returns an error if there is input left.
+    pub fn expect_eof(&mut self, expected: &[&'static str]) -> Result<(), ParseError> {
+        match self.peek() {
+            (Token::Eof, _) => Ok(()),
+            (t, span) => Err(ParseError::new(t, span, expected)),
+        }
+    }
+
+    fn lex(&mut self) -> (Token<'a>, Span) {
+        // Skip whitespace.
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+        let rest = &self.src[self.pos..];
+        let start = self.pos;
+
+        let Some(c) = rest.chars().next() else {
+            return (Token::Eof, Span { start, end: start });
+        };
+
+        let (token, len) = if c.is_alphabetic() {
+            let len = rest.find(|c: char| !c.is_alphanumeric())
+                .unwrap_or(rest.len());
+            (Token::Ident(&rest[..len]), len)
+        } else if c.is_ascii_digit() {
+            // The prefix, separators and suffix are part of the literal.
+            // It is checked when it is converted to a number.
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '\'')
+                .unwrap_or(rest.len());
+            (Token::Num(&rest[..len]), len)
+        } else if let Some(s) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            (Token::Sym(s), s.len())
+        } else {
+            (Token::Unknown(c), c.len_utf8())
+        };
+
+        self.pos += len;
+        (token, Span { start, end: self.pos })
+    }
+}
+
+/// An error that occurred during parsing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Location of the offending token in the input.
+    pub span: Span,
+
+    /// Descriptions of the tokens that would have been valid.
+    pub expected: Vec<&'static str>,
+
+    /// Description of the offending token.
+    pub found: String,
+}
+
+impl ParseError {
+    pub fn new(found: Token<'_>, span: Span, expected: &[&'static str]) -> Self {
+        Self {
+            span,
+            expected: expected.to_vec(),
+            found: found.to_string(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Expected ")?;
+        match self.expected.split_last() {
+            None => f.write_str("nothing")?,
+            Some((last, [])) => f.write_str(last)?,
+            Some((last, init)) => write!(f, "{} or {}", init.join(", "), last)?,
+        }
+        write!(f, ", found {}", self.found)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use std::num::Wrapping;
+    use crate::expr::Expr;
+    use super::*;
+
+    fn parse_err(s: &str) -> ParseError {
+        match Expr::<Wrapping<u8>>::from_string(s) {
+            Ok(_) => panic!("'{}' should not parse", s),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let mut l = Lexer::new(" x1<<0x1f_u8 ;\t€");
+        let span = |start, end| Span { start, end };
+        assert_eq!(l.peek(), (Token::Ident("x1"), span(1, 3)));
+        assert_eq!(l.next_token(), (Token::Ident("x1"), span(1, 3)));
+        assert_eq!(l.next_token(), (Token::Sym("<<"), span(3, 5)));
+        assert_eq!(l.next_token(), (Token::Num("0x1f_u8"), span(5, 12)));
+        assert_eq!(l.next_token(), (Token::Sym(";"), span(13, 14)));
+        assert_eq!(l.next_token(), (Token::Unknown('€'), span(15, 18)));
+        assert_eq!(l.next_token(), (Token::Eof, span(18, 18)));
+        assert_eq!(l.next_token(), (Token::Eof, span(18, 18)));
+    }
+
+    #[test]
+    fn unexpected_token() {
+        let e = parse_err("x + * y");
+        assert_eq!(e.span, Span { start: 4, end: 5 });
+        assert_eq!(e.found, "'*'");
+        assert_eq!(e.to_string(), "Expected variable, constant, '(', '~' or '-', found '*'");
+
+        let e = parse_err("x << y)");
+        assert_eq!(e.span, Span { start: 6, end: 7 });
+        assert_eq!(e.to_string(), "Expected operator or end of input, found ')'");
+
+        let e = parse_err("x 12");
+        assert_eq!(e.span, Span { start: 2, end: 4 });
+        assert_eq!(e.found, "'12'");
+    }
+
+    #[test]
+    fn end_of_input() {
+        let e = parse_err("x +");
+        assert_eq!(e.span, Span { start: 3, end: 3 });
+        assert_eq!(e.found, "end of input");
+
+        let e = parse_err("(x & y  ");
+        assert_eq!(e.span, Span { start: 8, end: 8 });
+        assert_eq!(e.to_string(), "Expected operator or ')', found end of input");
+
+        assert_eq!(parse_err("").span, Span { start: 0, end: 0 });
+    }
+
+    /// The spans are byte offsets, also after multi-byte characters.
+    #[test]
+    fn non_ascii() {
+        let s = "äb + € y";
+        let e = parse_err(s);
+        assert_eq!(e.span, Span { start: 6, end: 9 });
+        assert_eq!(&s[e.span.start..e.span.end], "€");
+        assert_eq!(e.found, "'€'");
+
+        let s = "αβ * (x ";
+        let e = parse_err(s);
+        assert_eq!(e.span, Span { start: s.len(), end: s.len() });
+    }
+}
@@ -0,0 +1,497 @@
+//! Permutation polynomials and their inverses.
+
+use std::ops::ShlAssign;
+
+use num_traits::{Num, NumAssign};
+use rand::Rng;
+use rand::distributions::{Standard, Distribution};
+
+use crate::congruence_solver;
+use crate::vector::Vector;
+use crate::matrix::Matrix;
+use crate::polynomial::Polynomial;
+use crate::numbers::{UniformNum, int_from_str};
+
+/// Invert using p as a generator.
+pub fn invert_fermat<T: UniformNum>(
+    p: &Polynomial<T>, zi: &ZeroIdeal<T>
+) -> Polynomial<T> {
+    // p^(2^i-1)
+    let mut f = p.clone();
+    for i in 0..zi.n {
+        // p^(2^i)
+        let g = compose(&f, p, zi).simplified(zi);
+        if g.is_id() {
+            // This will incorrectly say ord(X)=2, but whatever.
+            crate::log(&format!("log(ord(p)) = {}", i + 1));
+            return f;
+        }
+
+        f = compose(&f, &g, zi).simplified(zi);
+    }
+
+    panic!("Failed to invert {}", p)
+}
+
+/// Invert using Newton's method.
+pub fn invert_newton<T: UniformNum>(
+    p: &Polynomial<T>, zi: &ZeroIdeal<T>
+) -> Polynomial<T> {
+    // Initialize g with the initial guess Q(X)=X.
+    let mut q = Polynomial::from_coeffs(&[T::zero(), T::one()]);
+
+    let mut it = 0;
+
+    // Do the Newton iterations.
+    loop {
+        assert!(it <= zi.n * 2, "Failed to compute the inverse\
+                in a reasonable number of iterations.");
+
+        // Compute the composition.
+        let mut comp = compose(p, &q, zi).simplified(zi);
+
+        // Do we already have p(q(x)) = x?
+        if comp.is_id() {
+            crate::log(&format!("Inverted in {} iterations", it));
+            return q;
+        }
+
+        // Subtract X.
+        // This is the quantity we want to make 0.
+        comp.coeffs[1] -= T::one();
+
+        // Update the guess.
+        let qd = q.derivative();
+        q -= &(&qd * &comp);
+        q.simplify(zi);
+
+        it += 1;
+    }
+}
+
+/// Invert using interpolation.
+pub fn invert_lagrange<T: UniformNum>(
+    p: &Polynomial<T>, zi: &ZeroIdeal<T>
+) -> Polynomial<T> {
+    // Construct a system of linear congruences.
+    let rows = zi.gen.last().unwrap().len();
+    let cols = zi.gen.last().unwrap().len();
+
+    // Construct the Vandermonde matrix.
+    let mut a = Matrix::<T>::zero(rows, cols);
+    let mut i = T::zero();
+    for r in 0..rows {
+        let mut j = T::one();
+        let x = p.eval(i);
+        for c in 0..cols {
+            a[(r, c)] = j;
+            j *= x;
+        }
+
+        i += T::one();
+    }
+
+    // Construct the vector of values of the polynomial.
+    let mut b = Vector::<T>::zero(rows);
+    let mut i = T::zero();
+    for r in 0..rows {
+        b[r] = i;
+        i += T::one();
+    }
+
+    let l = congruence_solver::solve_congruences(a, &b);
+    //crate::log(&format!("The kernel has dimension {}.", l.basis.len()));
+    for b in &l.basis {
+        let k = Polynomial::from_coeffs(b.entries());
+        //assert!(k.simplified(zi).is_zero(),
+        //    "Polynomial in the kernel is non-null.");
+        if !k.clone().simplified(zi).is_zero() {
+            crate::log(&format!("Polynomial in kernel is not null: {}", k));
+        }
+    }
+
+    Polynomial::from_coeffs(l.offset.entries()).simplified(zi)
+}
+
+/// Computes the composition of two polynomials.
+pub fn compose<T: UniformNum>(
+    p: &Polynomial<T>,
+    q: &Polynomial<T>,
+    zi: &ZeroIdeal<T>
+) -> Polynomial<T> {
+    // We are using Horner's method to evaluate the polynomial `p` at `q(x)`.
+
+    // Iterate over the coefficients in reverse order.
+    let mut iter = p.coeffs.iter().rev();
+
+    // The last coefficient is the initial value.
+    let mut r = Polynomial::constant(iter.next().map_or(T::zero(), |c| *c));
+
+    for c in iter {
+        r *= q;
+        r += *c;
+        r.reduce(zi);
+    }
+
+    r
+}
+
+/// Used internally as a function to Iterator::fold.
+fn parity<T: UniformNum>(acc: bool, i: &T) -> bool {
+    match *i & T::one() != T::zero() {
+        true => !acc,
+        false => acc,
+    }
+}
+
+/// Is this a permutation polynomial?
+pub fn is_perm_poly<T: UniformNum>(f: &Polynomial<T>) -> bool {
+    f.coeffs.get(1).is_some_and(|i| *i & T::one() != T::zero())
+        && f.coeffs.iter().skip(2).step_by(2).fold(true, parity)
+        && f.coeffs.iter().skip(3).step_by(2).fold(true, parity)
+}
+
+/// Generates a random permutation polynomial.
+pub fn rand_perm_poly<T, R>(zi: &ZeroIdeal<T>, rng: &mut R) -> Polynomial<T>
+    where 
+        T: UniformNum + std::fmt::Display,
+        R: Rng,
+        Standard: Distribution<T>,
+{
+    // This is the smallest degree possible that can represent any permutation
+    // that has a polynomial representation.
+    let degree = zi.gen.last().unwrap().len() - 1;
+
+    // Create the polynomial. 
+    let mut p = Polynomial {
+        coeffs: vec![T::zero(); degree + 1]
+    };
+
+    // Initialize the coefficients with random values.
+    for c in &mut p.coeffs {
+        *c = rng.gen();
+    }
+
+    // a_1 has to be odd.
+    if p.coeffs[1] & T::one() == T::zero() {
+        p.coeffs[1] += T::one();
+    }
+
+    // a_2 + a_4 + ... has to be even.
+    if p.coeffs.iter().skip(2).step_by(2).fold(false, parity) {
+        let i = rng.gen_range(1..=degree as u32 / 2) as usize;
+        p.coeffs[2*i] += T::one();
+    }
+
+    // a_3 + a_5 + ... has to be even.
+    if p.coeffs.iter().skip(3).step_by(2).fold(false, parity) {
+        let i = rng.gen_range(1..=(degree as u32 - 1) / 2) as usize;
+        p.coeffs[2*i+1] += T::one();
+    }
+
+    p.simplify(zi);
+    p
+}
+
+/// Generates a random null polynomial, i.e. a non-zero polynomial
+/// that evaluates to zero everywhere.
+/// It is a combination of the generators of the zero ideal
+/// with random linear polynomials as the coefficients.
+pub fn rand_null_poly<T, R>(zi: &ZeroIdeal<T>, rng: &mut R) -> Polynomial<T>
+    where
+        T: UniformNum,
+        R: Rng,
+        Standard: Distribution<T>,
+{
+    loop {
+        let mut p = Polynomial::zero();
+        for g in &zi.gen {
+            let m = Polynomial::from_coeffs(&[rng.gen::<T>(), rng.gen()]);
+            p += &(&m * g);
+        }
+
+        // Reducing by the last generator keeps the polynomial null.
+        // If it is the only generator, like for 1 bit,
+        // the reduced polynomial would always be zero.
+        if zi.gen.len() > 1 {
+            p.reduce(zi);
+        } else {
+            p.truncate();
+        }
+        if !p.is_zero() {
+            return p;
+        }
+    }
+}
+
+/// Parse a polynomial.
+/// Either as a space-separated list of coefficients a_d ... a_0,
+/// or as a polynomial expression 4x^2 + 3x + 2.
+/// The coefficients can be any integer literals, e.g. `0xff`.
+pub fn parse_poly<T: UniformNum>(
+    mut poly: String
+) -> Result<Polynomial<T>, String> {
+    if !poly.is_ascii() {
+        return Err("Non-ascii input.".into());
+    }
+
+    poly.make_ascii_lowercase();
+
+    let parse_coeff = |c: &str| int_from_str::<T>(c).map_err(|e|
+        format!("Failed to parse coefficient '{}': Expected {}.", c, e)
+    );
+
+    // Is the x at index i part of the prefix of a hexadecimal literal.
+    let is_hex_prefix = |p: &[u8], i: usize| {
+        let in_literal = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b'\'';
+        i >= 1 && p[i - 1] == b'0'
+            && (i < 2 || !in_literal(p[i - 2]))
+            && p.get(i + 1).is_some_and(|c| c.is_ascii_hexdigit())
+    };
+
+    // If the polynomial contains x then it is an actual expression.
+    // Otherwise, it is a list of coefficients.
+    let p = poly.as_bytes();
+    let is_expr = (0..p.len()).any(|i| p[i] == b'x' && !is_hex_prefix(p, i));
+    let mut coeffs = Vec::with_capacity(8);
+    if is_expr {
+        poly.retain(|c| c != ' ');
+        let p = poly.as_bytes();
+        let mut i = 0;
+        let mut last_i = usize::MAX;
+        while i < p.len() {
+            if i == last_i {
+                return Err("Got stuck while parsing polynomial. This is a bug.".into());
+            }
+            last_i = i;
+
+            // Parse the sign.
+            let sign = match p[i] {
+                b'+' => { i += 1; false },
+                b'-' => { i += 1; true },
+                _ => false,
+            };
+
+            // Parse the coefficient.
+            let mut c = T::one();
+            if p[i].is_ascii_digit() {
+                let start = i;
+                if is_hex_prefix(p, i + 1) {
+                    i += 2;
+                    while i < p.len() && (p[i].is_ascii_hexdigit() || p[i] == b'_') {
+                        i += 1;
+                    }
+                } else {
+                    if p[i] == b'0' && matches!(p.get(i + 1), Some(b'b' | b'o')) {
+                        i += 2;
+                    }
+                    while i < p.len() && (p[i].is_ascii_digit() || p[i] == b'_') {
+                        i += 1;
+                    }
+                }
+
+                // The suffix, e.g. `u8`.
+                while i < p.len() && matches!(p[i], b'u' | b'i' | b'l' | b'0'..=b'9') {
+                    i += 1;
+                }
+
+                c = parse_coeff(&poly[start..i])?;
+
+                if i < p.len() && p[i] == b'*' {
+                    i += 1;
+                }
+            }
+
+            if sign {
+                c = T::zero() - c;
+            }
+
+            // Parse the exponent.
+            let mut e = 0;
+
+            // Skip past the `x`.
+            if i < p.len() && p[i] == b'x' {
+                i += 1;
+                e = 1;
+
+                // If there is an exponent, parse it.
+                if i < p.len() && p[i] == b'^' {
+                    i += 1;
+                    if !p[i].is_ascii_digit() {
+                        return Err("Failed to parse exponent.".into());
+                    }
+                    e = 0;
+                    while i < p.len() {
+                        if !p[i].is_ascii_digit() {
+                            break;
+                        }
+
+                        e *= 10;
+                        e += (p[i] - b'0') as usize;
+                        i += 1;
+                    }
+                }
+            }
+
+            if e >= coeffs.len() {
+                coeffs.resize(e + 1, T::zero());
+            }
+
+            coeffs[e] = c;
+        }
+    } else {
+        for c in poly.split_ascii_whitespace() {
+            coeffs.push(parse_coeff(c)?);
+        }
+        coeffs.reverse();
+    }
+
+    let p = Polynomial { coeffs };
+
+    Ok(p.truncated())
+}
+
+/// The ideal of all polynomial expressions that evaluate to 0.
+pub struct ZeroIdeal<T> {
+    /// Mod 2^n.
+    n: usize,
+
+    /// The generators of the ideal.
+    gen: Vec<Polynomial<T>>,
+}
+
+impl<T: UniformNum> ZeroIdeal<T> {
+    pub fn init() -> Self {
+        let n = T::bits() as usize;
+
+        let mut gen = Vec::new();
+
+        // div stores how often 2 divides i!.
+        // It is successively updated.
+        let mut div = 0usize;
+        for i in (2usize..).step_by(2) {
+            div += i.trailing_zeros() as usize;
+
+            // If the exponent would be negative
+            // then add the last generator and stop.
+            if n <= div {
+                let mut p = Polynomial::<T>::one();
+
+                let mut j = T::zero();
+                for _ in 0..i {
+                    // Multiply the current polynomial by (x-j).
+                    p.mul_lin(j);
+                    j += T::one();
+                }
+
+                p.truncate();
+
+                gen.push(p);
+                break;
+            }
+
+            // Compute the exponent.
+            let e = n - div;
+
+            // Let's build the polynomial.
+            let mut p = Polynomial::<T>::one();
+
+            let mut j = T::zero();
+            for _ in 0..i {
+                // Multiply the current polynomial by (x-j).
+                p.mul_lin(j);
+                j += T::one();
+            }
+
+            p <<= e;
+            p.truncate();
+
+            gen.push(p);
+        }
+
+        Self { n, gen }
+    }
+}
+
+impl<T: UniformNum> Polynomial<T> {
+    /// Returns a simplified polynomial.
+    pub fn simplified(mut self, zi: &ZeroIdeal<T>) -> Self {
+        self.simplify(zi);
+        self
+    }
+
+    /// Simplifies a polynomial by adding a polynomial in the zero ideal.
+    pub fn simplify(&mut self, zi: &ZeroIdeal<T>) {
+        let mut coeff = self.len() - 1;
+
+        for gen in zi.gen.iter().rev() {
+            let gen_len = gen.len() - 1;
+
+            while coeff >= gen_len {
+                let m = self.coeffs[coeff] / gen.coeffs[gen_len];
+                if m != T::zero() {
+                    let iter = self.coeffs[coeff-gen_len..=coeff]
+                        .iter_mut().zip(gen.coeffs.iter());
+
+                    for (p, g) in iter {
+                        *p -= m * *g;
+                    }
+                }
+                coeff -= 1;
+            }
+        }
+
+        self.truncate();
+    }
+
+    /// Reduce the degree of the polynomial as much as possible
+    /// using the generator of the highest degree.
+    pub fn reduce(&mut self, zi: &ZeroIdeal<T>) {
+        let gen = zi.gen.last().unwrap();
+        let gen_len = gen.len() - 1;
+        while self.len() >= gen.len() {
+            let c = self.coeffs.pop().unwrap();
+            for i in 0..gen_len {
+                let j = self.len() - gen_len + i;
+                self.coeffs[j] -= c * gen.coeffs[i];
+            }
+        }
+
+        self.truncate();
+    }
+}
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use crate::numbers::{Width, with_width};
+    use super::*;
+
+    /// Checks that random null polynomials are non-zero and evaluate to zero,
+    /// on all inputs for small widths and random ones otherwise.
+    fn check_null_poly<T: UniformNum>() where Standard: Distribution<T> {
+        let zi = ZeroIdeal::<T>::init();
+        let mut rng = ChaCha8Rng::seed_from_u64(T::bits() as u64);
+        for _ in 0..8 {
+            let p = rand_null_poly(&zi, &mut rng);
+            assert!(!p.is_zero());
+            if T::bits() <= 8 {
+                for x in 0..1u128 << T::bits() {
+                    assert!(p.eval(T::from_u128(x)).is_zero(), "{} at {}", p, x);
+                }
+            } else {
+                for _ in 0..256 {
+                    let x = rng.gen();
+                    assert!(p.eval(x).is_zero(), "{} at {}", p, x);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn null_poly_all_widths() {
+        for bits in 1..=128 {
+            with_width!(Width::new(bits).unwrap(), check_null_poly())
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::ops::{Add, AddAssign, Sub, Mul, MulAssign, SubAssign, ShlAssign};
 use std::fmt::{self, Write, Display, Formatter};
+use std::rc::Rc;
 use num_traits::{Num, NumAssign};
+use crate::expr::Expr;
 
 #[derive(Debug, Clone)]
 pub struct Polynomial<T> {
@@ -23,6 +25,7 @@ impl<T> Polynomial<T> {
     }
 
     /// The number of coefficients.
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         self.coeffs.len()
     }
@@ -51,7 +54,7 @@ impl<T: NumAssign + Copy> Polynomial<T> {
 
     /// Is this the identity polynomial P(X)=X?
     pub fn is_id(&self) -> bool {
-        self.coeffs == &[T::zero(), T::one()]
+        self.coeffs == [T::zero(), T::one()]
     }
 
     /// Removes leading zero coefficients.
@@ -71,6 +74,29 @@ impl<T: NumAssign + Copy> Polynomial<T> {
         self
     }
 
+    /// Returns an expression that evaluates the polynomial at `x`
+    /// using Horner's method.
+    pub fn to_expr(&self, x: Rc<Expr<T>>) -> Expr<T> {
+        // Iterate over the coefficients in reverse order.
+        let mut iter = self.coeffs.iter().rev();
+
+        // The last coefficient is the initial value.
+        let mut e = Rc::new(Expr::Const(iter.next().map_or(T::zero(), |c| *c)));
+
+        for c in iter {
+            e = match *e {
+                Expr::Const(c) if c == T::one() => x.clone(),
+                _ => Rc::new(Expr::Mul(e, x.clone())),
+            };
+
+            if *c != T::zero() {
+                e = Rc::new(Expr::Add(e, Rc::new(Expr::Const(*c))));
+            }
+        }
+
+        Rc::try_unwrap(e).unwrap_or_else(|e| e.as_ref().clone())
+    }
+
     /// Evaluate the polynomial at x.
     pub fn eval(&self, x: T) -> T {
         // This is Horner's method.
@@ -154,7 +180,7 @@ impl<T: Num + Copy + Display> Polynomial<T> {
         };
 
         for (e, c) in iter {
-            s.push_str("+");
+            s.push('+');
             write_term(&mut s, e, *c);
         }
 
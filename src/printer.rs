@@ -3,14 +3,14 @@
 
 use std::fmt::{self, Display, Write, Formatter};
 use crate::uniform_expr::{UExpr, LUExpr};
-use crate::pages::Width;
 use crate::numbers::UniformNum;
 
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use num_traits::{Zero, One};
 
 /// Determines how the result will be printed.
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Printer {
     /// Some default.
@@ -24,6 +24,19 @@ pub enum Printer {
 
     /// Tex expression.
     Tex,
+
+    /// Print an LLVM IR function.
+    LlvmIr,
+
+    /// Print a python function.
+    Python,
+
+    /// Print a JavaScript function.
+    /// Widths over 32 bits use BigInts.
+    JavaScript,
+
+    /// Print an SMT-LIB function over bit vectors.
+    SmtLib,
 }
 
 impl Printer {
@@ -33,72 +46,88 @@ impl Printer {
         UExprPrinter { p: self, e }
     }
 
+    /// Does the printed code have to clear the high bits
+    /// because the language's integers are wider.
+    pub(crate) fn needs_mask(self, bits: u32) -> bool {
+        match self {
+            Printer::Rust => bits < 8 || !bits.is_power_of_two(),
+            // The C function computes with `unsigned` instead.
+            Printer::C => bits < 32 && !c_is_bitint(bits),
+            Printer::Python | Printer::JavaScript => true,
+            _ => false,
+        }
+    }
+
+    /// Can the printer print conditions, see [`Expr::print_as_condition`].
+    ///
+    /// [`Expr::print_as_condition`]: crate::expr::Expr::print_as_condition
+    pub fn supports_conditions(self) -> bool {
+        matches!(self, Printer::Default | Printer::C | Printer::Rust)
+    }
+
+    /// Prints a uniform expression.
+    /// The printers that don't have their own syntax
+    /// for uniform expressions use the default one.
     pub fn print_uexpr(self, e: &UExpr) -> String {
         self.u(e).to_string()
     }
 
-    pub fn print_luexpr<T: UniformNum>(self, e: &LUExpr<T>) -> String {
+    /// Prints a linear combination of uniform expressions.
+    /// If `signed` is set, the functions take and return signed integers.
+    pub fn print_luexpr<T: UniformNum>(self, e: &LUExpr<T>, signed: bool) -> String {
         let mut s = String::with_capacity(e.0.len() * 8);
         match self {
+            // The signed functions have to compute with unsigned integers
+            // or `Wrapping`, which the general printer takes care of.
+            Printer::C | Printer::Rust if signed => {
+                s = e.to_expr().print_as_fn(self, signed)
+            },
             Printer::C | Printer::Default => {
-                let ty = match std::mem::size_of::<T>() {
-                    1 => "uint8_t",
-                    2 => "uint16_t",
-                    4 => "uint32_t",
-                    8 => "uint64_t",
-                    16 => "uint128_t",
-                    s => panic!("Unsupported size: {s}"),
-                };
-
-                let vars = e.vars();
-                write!(&mut s, "{} f(", ty);
-                for v in &vars {
-                    write!(&mut s, "{} {}, ", ty, v);
+                let ty = c_type(T::bits(), false);
+                let args: Vec<_> = e.vars().iter()
+                    .map(|v| format!("{} {}", ty, v))
+                    .collect();
+                write!(&mut s, "{} f({}) {{\n\treturn ", ty, args.join(", "));
+                // Without the suffix, narrow integers would be promoted to int
+                // and the products could overflow.
+                if self == Printer::C {
+                    self.print_luexpr_impl(&mut s, e, |i| c_const(i.to_u128(), T::bits()));
+                } else {
+                    self.print_luexpr_impl(&mut s, e, |i| i.to_string());
                 }
-
-                // Remove the last ', '.
-                s.pop();
-                s.pop();
-                s += ") {\n\treturn ";
-                self.print_luexpr_impl(&mut s, e, "");
                 s += ";\n}"
             },
             Printer::Rust => {
-                let (ty, const_suffix) = match std::mem::size_of::<T>() {
-                    1   => ("Wrapping<u8>", "u8"),
-                    2   => ("Wrapping<u16>", "u16"),
-                    4   => ("Wrapping<u32>", "u32"),
-                    8   => ("Wrapping<u64>", "u64"),
-                    16  => ("Wrapping<u128>", "u128"),
-                    _ => panic!("Unsupported size: {s}"),
-                };
-
-                let vars = e.vars();
-                s += "fn f(";
-                for v in &vars {
-                    write!(&mut s, "{}: {},", v, ty);
+                let const_suffix = rust_int(T::bits(), false);
+                let fmt_const = |i: T| format!("Wrapping({}{})", i, const_suffix);
+                let ty = format!("Wrapping<{}>", const_suffix);
+                let args: Vec<_> = e.vars().iter()
+                    .map(|v| format!("{}: {}", v, ty))
+                    .collect();
+                write!(&mut s, "fn f({}) -> {} {{\n\t", args.join(", "), ty);
+                if self.needs_mask(T::bits()) {
+                    s += "(";
+                    self.print_luexpr_impl(&mut s, e, fmt_const);
+                    write!(&mut s, ") & Wrapping({:#x})", mask(T::bits()));
+                } else {
+                    self.print_luexpr_impl(&mut s, e, fmt_const);
                 }
-
-                // Remove the last ', '.
-                s.pop();
-                s.pop();
-                s += ") -> ";
-                s += ty;
-                s += " {\n\t";
-                self.print_luexpr_impl(&mut s, e, const_suffix);
                 s += "\n}"
             },
-            Printer::Tex => self.print_luexpr_impl(&mut s, e, ""),
+            Printer::Tex => self.print_luexpr_impl(&mut s, e, |i| i.to_string()),
+            Printer::LlvmIr | Printer::Python | Printer::JavaScript | Printer::SmtLib => {
+                s = e.to_expr().print_as_fn(self, signed)
+            },
         }
 
-        return s;
+        s
     }
 
     fn print_luexpr_impl<T: UniformNum>(
         self,
         s: &mut String,
         e: &LUExpr<T>,
-        const_suffix: &str,
+        fmt_const: impl Fn(T) -> String,
     ) {
         let mut iter = e.0.iter()
             .filter(|(i, _)| *i != T::zero())
@@ -119,24 +148,16 @@ impl Printer {
                     write!(s, "({})", e);
                 }
             } else {
-                if self == Self::Rust {
-                    if unary {
-                        write!(s, "Wrapping({}{})*{}", i, const_suffix, e);
-                    } else {
-                        write!(s, "Wrapping({}{})*({})", i, const_suffix, e);
-                    }
-                    return;
-                }
-
                 let op = match self {
-                    Self::Default | Self::C | Self::Rust => "*",
+                    Self::Default | Self::C | Self::Rust | Self::LlvmIr
+                        | Self::Python | Self::JavaScript | Self::SmtLib => "*",
                     Self::Tex => "\\cdot ",
                 };
 
                 if unary {
-                    write!(s, "{}{}{}", i, op, e);
+                    write!(s, "{}{}{}", fmt_const(i), op, e);
                 } else {
-                    write!(s, "{}{}({})", i, op, e);
+                    write!(s, "{}{}({})", fmt_const(i), op, e);
                 }
             }
         };
@@ -160,6 +181,80 @@ impl Printer {
     }
 }
 
+/// The C type of integers with the number of bits.
+/// Unusual widths use C23's `_BitInt`
+/// and 128 bits the `__int128` of GCC and Clang.
+pub(crate) fn c_type(bits: u32, signed: bool) -> String {
+    match (bits, c_is_bitint(bits), signed) {
+        (_, true, false) => format!("unsigned _BitInt({})", bits),
+        (_, true, true) => format!("_BitInt({})", bits),
+        (128, _, false) => "unsigned __int128".to_owned(),
+        (128, _, true) => "__int128".to_owned(),
+        (_, _, false) => format!("uint{}_t", bits),
+        (_, _, true) => format!("int{}_t", bits),
+    }
+}
+
+/// Prints an unsigned C constant.
+/// Without the suffix, constants would be signed and the operations could overflow.
+/// C has no 128-bit literals, so larger constants are put together from two halves.
+pub(crate) fn c_const(v: u128, bits: u32) -> String {
+    if c_is_bitint(bits) {
+        format!("{}uwb", v)
+    } else if v >> 64 != 0 {
+        format!("((({}){:#x}u << 64) | {:#x}u)", c_type(bits, false), v >> 64, v as u64)
+    } else {
+        format!("{}u", v)
+    }
+}
+
+/// The type the C function computes with.
+/// Narrower integers would be promoted to int, whose overflow is undefined.
+pub(crate) fn c_compute_type(bits: u32) -> String {
+    match bits {
+        8 | 16 => "unsigned".to_owned(),
+        _ => c_type(bits, false),
+    }
+}
+
+/// Is the C type a `_BitInt`.
+/// Constants then need the `uwb` suffix.
+pub(crate) fn c_is_bitint(bits: u32) -> bool {
+    !matches!(bits, 8 | 16 | 32 | 64 | 128)
+}
+
+/// The smallest Rust integer type with at least the number of bits.
+pub(crate) fn rust_int(bits: u32, signed: bool) -> &'static str {
+    match (bits, signed) {
+        (0..=8, false) => "u8",
+        (9..=16, false) => "u16",
+        (17..=32, false) => "u32",
+        (33..=64, false) => "u64",
+        (_, false) => "u128",
+        (0..=8, true) => "i8",
+        (9..=16, true) => "i16",
+        (17..=32, true) => "i32",
+        (33..=64, true) => "i64",
+        (_, true) => "i128",
+    }
+}
+
+/// The number of bits of the Rust integer type for the width.
+pub(crate) fn rust_bits(bits: u32) -> u32 {
+    bits.next_power_of_two().max(8)
+}
+
+/// Does the JavaScript code use BigInts,
+/// because numbers can't represent the integers exactly.
+pub(crate) fn js_bigint(bits: u32) -> bool {
+    bits > 32
+}
+
+/// The mask that clears the bits above the width.
+pub(crate) fn mask(bits: u32) -> u128 {
+    u128::MAX >> (128 - bits)
+}
+
 struct UExprPrinter<'a> {
     p: Printer,
     e: &'a UExpr,
@@ -199,8 +294,8 @@ impl<'a> Display for UExprPrinter<'a> {
             Not(i) => {
                 let i = self.u(i);
                 match self.p {
-                    Default | C if i.e.is_unary() => write!(f, "~{}", i),
-                    Default | C => write!(f, "~({})", i),
+                    Default | C | LlvmIr | Python | JavaScript | SmtLib if i.e.is_unary() => write!(f, "~{}", i),
+                    Default | C | LlvmIr | Python | JavaScript | SmtLib => write!(f, "~({})", i),
                     Rust if i.e.is_unary() => write!(f, "!{}", i),
                     Rust => write!(f, "!({})", i),
                     Tex => write!(f, "\\overline{{{}}}", i),
@@ -208,19 +303,22 @@ impl<'a> Display for UExprPrinter<'a> {
             },
             And(l, r) => {
                 match self.p {
-                    Default | C | Rust => self.write_safe(l, r, "&", f),
+                    Default | C | Rust | LlvmIr | Python | JavaScript | SmtLib
+                        => self.write_safe(l, r, "&", f),
                     Tex => self.write_safe(l, r, "\\land", f),
                 }
             },
             Or(l, r) => {
                 match self.p {
-                    Default | C | Rust => self.write_safe(l, r, "|", f),
+                    Default | C | Rust | LlvmIr | Python | JavaScript | SmtLib
+                        => self.write_safe(l, r, "|", f),
                     Tex => self.write_safe(l, r, "\\lor", f),
                 }
             },
             Xor(l, r) => {
                 match self.p {
-                    Default | C | Rust => self.write_safe(l, r, "^", f),
+                    Default | C | Rust | LlvmIr | Python | JavaScript | SmtLib
+                        => self.write_safe(l, r, "^", f),
                     Tex => self.write_safe(l, r, "\\oplus", f),
                 }
             }
@@ -0,0 +1,298 @@
+//! Simplification of linear MBA expressions.
+//!
+//! A linear MBA expression is uniquely determined by its values on the
+//! valuations where each variable is either 0 or -1 (all bits set).
+//! These values are called its signature.
+//! To simplify an expression, we write its signature
+//! as a linear combination of the signatures of a basis,
+//! or as a multiple of a single uniform expression if that is shorter.
+
+use crate::expr::Expr;
+use crate::matrix::Matrix;
+use crate::vector::Vector;
+use crate::printer::Printer;
+use crate::numbers::{UniformNum, Width, with_width};
+use crate::uniform_expr::{LUExpr, UExpr, Valuation};
+use crate::congruence_solver::solve_congruences;
+use crate::obfuscate::ObfuscationError;
+use crate::synthesis::{TruthTable, synthesize, cost};
+
+/// The uniform expressions in which simplified expressions are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Basis {
+    /// -1 and the conjunctions of all non-empty sets of variables,
+    /// e.g. `x`, `y`, `x & y`.
+    Conjunction,
+
+    /// -1 and the disjunctions of all non-empty sets of variables,
+    /// e.g. `x`, `y`, `x | y`.
+    Disjunction,
+}
+
+impl Basis {
+    /// Returns the 2^n expressions in the basis for the variables.
+    pub fn uexprs(self, vars: &[String]) -> Vec<UExpr> {
+        (0..1usize << vars.len()).map(|s| {
+            let mut it = vars.iter()
+                .enumerate()
+                .filter(|(i, _)| (s >> i) & 1 == 1)
+                .map(|(_, v)| UExpr::Var(v.clone()));
+
+            let Some(first) = it.next() else {
+                return UExpr::Ones;
+            };
+
+            it.fold(first, |acc, v| match self {
+                Basis::Conjunction => UExpr::and(acc, v),
+                Basis::Disjunction => UExpr::or(acc, v),
+            })
+        }).collect()
+    }
+}
+
+/// Evaluates `f` on all valuations where the variables are 0 or -1.
+/// In the i-th valuation, the j-th variable is -1 iff the j-th bit of i is set.
+pub fn signature<T, F>(vars: &[String], mut f: F) -> Vector<T>
+    where
+        T: UniformNum,
+        F: FnMut(&Valuation<T>) -> T,
+{
+    let mut val = Valuation::zero(vars.to_vec());
+    let mut s = Vector::zero(1 << vars.len());
+    for i in 0..s.dim {
+        for (j, v) in vars.iter().enumerate() {
+            val[v] = if (i >> j) & 1 == 0 {
+                T::zero()
+            } else {
+                T::zero() - T::one()
+            };
+        }
+
+        s[i] = f(&val);
+    }
+
+    s
+}
+
+/// Writes the function with the given signature over `vars`
+/// as a linear combination of `ops`.
+/// Terms with a zero coefficient are omitted.
+/// Returns None if this isn't possible.
+///
+/// If the operations are linearly dependent,
+/// then the result is not necessarily the shortest one.
+pub fn express_in<T: UniformNum>(
+    sig: &Vector<T>, vars: &[String], ops: &[UExpr]
+) -> Option<LUExpr<T>> {
+    let mut a = Matrix::zero(sig.dim, ops.len());
+    for (j, op) in ops.iter().enumerate() {
+        let s = signature(vars, |v| op.eval(v));
+        for (i, e) in s.iter().enumerate() {
+            a[(i, j)] = *e;
+        }
+    }
+
+    let l = solve_congruences(a, sig);
+    if l.is_empty() {
+        return None;
+    }
+
+    Some(LUExpr(l.offset.iter()
+        .zip(ops)
+        .filter(|(c, _)| !c.is_zero())
+        .map(|(c, op)| (*c, op.clone()))
+        .collect()))
+}
+
+/// Rewrites the expression as a linear combination of the basis,
+/// unless it is a multiple of a single uniform expression plus a constant.
+/// The result only contains the variables that actually matter
+/// and there is no shorter way to write it in this basis.
+pub fn simplify_luexpr<T: UniformNum>(e: &LUExpr<T>, basis: Basis) -> LUExpr<T> {
+    let vars = e.vars();
+    let sig = signature(&vars, |v| e.eval(v));
+    simplify_signature(&sig, &vars, basis)
+}
+
+/// Rewrites a linear MBA expression like [`simplify_luexpr`].
+/// Returns None if the expression is not a linear MBA expression,
+/// i.e. if it is not a linear combination of bitwise expressions.
+pub fn simplify_expr<T: UniformNum>(e: &Expr<T>, basis: Basis) -> Option<LUExpr<T>> {
+    if !is_linear(e) {
+        return None;
+    }
+
+    let vars = e.vars();
+    let sig = signature(&vars, |v| e.eval(v, false));
+    Some(simplify_signature(&sig, &vars, basis))
+}
+
+/// Parses a linear MBA expression, simplifies it and prints the result.
+/// Linear MBA expressions compute the same for signed and unsigned integers,
+/// so `signed` only changes how the result is printed.
+pub fn simplify(
+    expr: &str, width: Width, basis: Basis, printer: Printer, signed: bool
+) -> Result<String, ObfuscationError> {
+    with_width!(width, simplify_impl(expr, basis, printer, signed))
+}
+
+fn simplify_impl<T: UniformNum>(
+    expr: &str, basis: Basis, printer: Printer, signed: bool
+) -> Result<String, ObfuscationError> {
+    let e = Expr::<T>::from_string(expr)?;
+    let s = simplify_expr(&e, basis)
+        .ok_or("The expression is not a linear MBA expression.")?;
+    Ok(s.to_expr().print_as_fn(printer, signed))
+}
+
+fn simplify_signature<T: UniformNum>(
+    sig: &Vector<T>, vars: &[String], basis: Basis
+) -> LUExpr<T> {
+    // The basis can represent every signature uniquely,
+    // so this never fails and the result is unique.
+    let mut e = express_in(sig, vars, &basis.uexprs(vars))
+        .expect("The basis does not span all linear MBA expressions.");
+
+    // If the signature only takes two values a and b,
+    // then it is (a-b)*u + a for the uniform expression u
+    // that is true exactly where the signature is b.
+    // This is often shorter, e.g. for `x ^ y`.
+    // Fewer terms are better, then fewer operators.
+    let len = |e: &LUExpr<T>| (e.0.len(), e.0.iter().map(|(_, u)| cost(u)).sum::<usize>());
+    let a = sig[0];
+    if let Some(&b) = sig.iter().find(|s| **s != a) {
+        if sig.iter().all(|s| *s == a || *s == b) {
+            for (a, b) in [(a, b), (b, a)] {
+                let t = TruthTable::from_fn(vars.len(), |i| sig[i] == b);
+                let mut f = LUExpr(vec![(a - b, synthesize(&t, vars))]);
+                if !a.is_zero() {
+                    f.0.push((T::zero() - a, UExpr::Ones));
+                }
+
+                if len(&f) < len(&e) {
+                    e = f;
+                }
+            }
+        }
+    }
+
+    // Put the constant at the end.
+    e.0.sort_by_key(|(_, u)| *u == UExpr::Ones);
+    e
+}
+
+/// Is the expression a linear combination of bitwise expressions.
+fn is_linear<T: UniformNum>(e: &Expr<T>) -> bool {
+    match e {
+        Expr::Add(l, r) | Expr::Sub(l, r) => is_linear(l) && is_linear(r),
+        Expr::Neg(i) => is_linear(i),
+        Expr::Mul(l, r) => match (l.as_ref(), r.as_ref()) {
+            (Expr::Const(_), e) | (e, Expr::Const(_)) => is_linear(e),
+            _ => false,
+        },
+        Expr::Const(_) => true,
+        e => is_bitwise(e),
+    }
+}
+
+/// Is the expression a bitwise expression,
+/// i.e. does each bit of the result only depend
+/// on the same bit of the variables.
+fn is_bitwise<T: UniformNum>(e: &Expr<T>) -> bool {
+    match e {
+        Expr::Var(_) => true,
+        Expr::Const(c) => c.is_zero() || *c == T::zero() - T::one(),
+        Expr::Not(i) => is_bitwise(i),
+        Expr::And(l, r) | Expr::Or(l, r) | Expr::Xor(l, r)
+            => is_bitwise(l) && is_bitwise(r),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::Wrapping;
+    use rand::{Rng, SeedableRng};
+    use rand::distributions::{Distribution, Standard};
+    use rand_chacha::ChaCha8Rng;
+    use crate::numbers::UInt;
+    use super::*;
+
+    const LINEAR: &[&str] = &[
+        "(x ^ y) + 2 * (x & y)",
+        "3 * (x | ~y) - 5 * (x ^ z) + 7",
+        "-(x & y & z) + 2 * ~(x | y) - (y ^ z) * 9",
+        "x - (x & y) + 4 * ~x - 3 * -1",
+        "(x & y) - (x | y) + (x ^ y)",
+    ];
+
+    /// Checks that the simplified expressions evaluate like the originals.
+    fn check_equivalent<T: UniformNum>()
+        where Standard: Distribution<T>
+    {
+        let mut rng = ChaCha8Rng::seed_from_u64(T::bits() as u64);
+        for s in LINEAR {
+            let e = Expr::<T>::from_string(s).unwrap();
+            let vars = e.vars();
+            for basis in [Basis::Conjunction, Basis::Disjunction] {
+                let l = simplify_expr(&e, basis).unwrap();
+                let mut val = Valuation::zero(vars.clone());
+                for _ in 0..64 {
+                    for v in &vars {
+                        val[v] = rng.gen();
+                    }
+                    assert!(l.eval(&val) == e.eval(&val, false), "{} is not {}", l, s);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn equivalent() {
+        check_equivalent::<Wrapping<u8>>();
+        check_equivalent::<Wrapping<u32>>();
+        check_equivalent::<Wrapping<u128>>();
+        UInt::with_width(5, check_equivalent::<UInt>);
+    }
+
+    #[test]
+    fn canonical_forms() {
+        let s = |e: &str| simplify(e, Width::U8, Basis::Conjunction, Printer::Default, false);
+        assert_eq!(s("(x ^ y) + 2 * (x & y)").unwrap(), "x + y");
+        assert_eq!(s("(x | y) - (x & y)").unwrap(), "x ^ y");
+        assert_eq!(s("x - (x & y)").unwrap(), "x & ~y");
+        assert_eq!(s("3 * (x & ~x)").unwrap(), "0");
+        assert!(s("x * y").is_err());
+    }
+
+    #[test]
+    fn express() {
+        type T = Wrapping<u8>;
+        let vars = ["x".to_owned(), "y".to_owned()];
+        let e = Expr::<T>::from_string("(x ^ y) + 2 * (x & y)").unwrap();
+        let sig = signature(&vars, |v| e.eval(v, false));
+
+        let x = UExpr::Var("x".to_owned());
+        let y = UExpr::Var("y".to_owned());
+        let l = express_in(&sig, &vars, &[x.clone(), y.clone()]).unwrap();
+        assert_eq!(l.0, vec![(Wrapping(1), x.clone()), (Wrapping(1), y.clone())]);
+
+        // x + y isn't a multiple of x & y.
+        assert!(express_in(&sig, &vars, &[UExpr::and(x, y)]).is_none());
+    }
+
+    #[test]
+    fn linear_and_bitwise() {
+        type T = Wrapping<u8>;
+        let e = |s: &str| Expr::<T>::from_string(s).unwrap();
+        assert!(is_linear(&e("3 * (x & y) - ~z + 5")));
+        assert!(is_linear(&e("-(x ^ y) * 2")));
+        assert!(!is_linear(&e("x * y")));
+        assert!(!is_linear(&e("x << 1")));
+        assert!(!is_linear(&e("(x + y) & z")));
+
+        assert!(is_bitwise(&e("x & ~(y | 255) ^ 0")));
+        assert!(!is_bitwise(&e("x & 2")));
+        assert!(!is_bitwise(&e("x + y")));
+    }
+}
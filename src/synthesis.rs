@@ -0,0 +1,312 @@
+//! Finding small uniform expressions for truth tables.
+//!
+//! For up to three variables, the smallest expression for every truth table
+//! is found by enumerating expressions by the number of operators.
+//! For more variables, the function is decomposed on one variable
+//! and the cofactors are synthesized recursively.
+
+use std::collections::HashMap;
+use std::num::Wrapping;
+use std::sync::OnceLock;
+use crate::uniform_expr::{UExpr, Valuation};
+
+/// Up to this many variables, the smallest expression is found.
+const EXACT_VARS: usize = 3;
+
+/// A boolean function given by its values on all inputs.
+/// In the i-th row, the j-th variable is true iff the j-th bit of i is set.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TruthTable {
+    /// The number of variables.
+    vars: usize,
+
+    /// The value for each row.
+    rows: Vec<bool>,
+}
+
+impl TruthTable {
+    /// Creates a truth table from the values of the 2^vars rows.
+    pub fn new(vars: usize, rows: Vec<bool>) -> Self {
+        assert!(rows.len() == 1 << vars, "A truth table over {} variables \
+            needs {} rows.", vars, 1usize << vars);
+        Self { vars, rows }
+    }
+
+    /// Creates a truth table by evaluating `f` for every row.
+    pub fn from_fn<F: FnMut(usize) -> bool>(vars: usize, f: F) -> Self {
+        Self { vars, rows: (0..1 << vars).map(f).collect() }
+    }
+
+    /// Returns the truth table of a uniform expression.
+    /// `vars` has to contain all variables of the expression.
+    pub fn of_uexpr(e: &UExpr, vars: &[String]) -> Self {
+        // Because the expression is uniform, evaluating it on a single bit
+        // gives its truth table. -1 means true and 0 means false.
+        let mut val = Valuation::<Wrapping<u8>>::zero(vars.to_vec());
+        Self::from_fn(vars.len(), |i| {
+            for (j, v) in vars.iter().enumerate() {
+                val[v] = Wrapping(0u8.wrapping_sub(((i >> j) & 1) as u8));
+            }
+            e.eval(&val).0 & 1 == 1
+        })
+    }
+
+    /// The number of variables.
+    pub fn num_vars(&self) -> usize {
+        self.vars
+    }
+
+    /// The values of all rows.
+    pub fn rows(&self) -> &[bool] {
+        &self.rows
+    }
+
+    /// Is the function always false.
+    pub fn is_false(&self) -> bool {
+        self.rows.iter().all(|r| !r)
+    }
+
+    /// Is the function always true.
+    pub fn is_true(&self) -> bool {
+        self.rows.iter().all(|r| *r)
+    }
+
+    /// Returns the rows as a number if they fit into a usize.
+    fn mask(&self) -> usize {
+        self.rows.iter()
+            .enumerate()
+            .fold(0, |m, (i, r)| m | (*r as usize) << i)
+    }
+
+    /// The functions obtained by setting the last variable to false or true.
+    fn cofactors(&self) -> (Self, Self) {
+        let half = self.rows.len() / 2;
+        let f = |rows: &[bool]| Self { vars: self.vars - 1, rows: rows.to_vec() };
+        (f(&self.rows[..half]), f(&self.rows[half..]))
+    }
+
+    fn zip(&self, o: &Self, f: impl Fn(bool, bool) -> bool) -> Self {
+        let rows = self.rows.iter().zip(&o.rows).map(|(a, b)| f(*a, *b)).collect();
+        Self { vars: self.vars, rows }
+    }
+}
+
+/// Returns a small uniform expression with the truth table
+/// where the i-th variable of the table is `vars[i]`.
+/// For at most three variables, there is no expression with fewer operators.
+pub fn synthesize(t: &TruthTable, vars: &[String]) -> UExpr {
+    assert!(t.vars == vars.len(), "Every variable of the truth table needs a name.");
+    synthesize_impl(t, vars, &mut HashMap::new())
+}
+
+fn synthesize_impl(
+    t: &TruthTable, vars: &[String], memo: &mut HashMap<TruthTable, UExpr>
+) -> UExpr {
+    if t.vars <= EXACT_VARS {
+        return instantiate(&exact(t.vars)[t.mask()], vars);
+    }
+
+    if let Some(e) = memo.get(t) {
+        return e.clone();
+    }
+
+    // Shannon decomposition on the last variable:
+    // t = (v & t1) | (~v & t0).
+    let v = UExpr::Var(vars[t.vars - 1].clone());
+    let (t0, t1) = t.cofactors();
+    let mut syn = |t: &TruthTable| synthesize_impl(t, &vars[..t.vars], memo);
+
+    let e = if t0 == t1 {
+        syn(&t0)
+    } else if t0.is_false() {
+        if t1.is_true() { v } else { UExpr::and(v, syn(&t1)) }
+    } else if t1.is_false() {
+        let nv = UExpr::not(v);
+        if t0.is_true() { nv } else { UExpr::and(nv, syn(&t0)) }
+    } else if t0.is_true() {
+        UExpr::or(UExpr::not(v), syn(&t1))
+    } else if t1.is_true() {
+        UExpr::or(v, syn(&t0))
+    } else {
+        let e0 = syn(&t0);
+        let e1 = syn(&t1);
+        let g = t0.zip(&t1, |a, b| a ^ b);
+
+        let mut candidates = vec![
+            // t = t0 ^ (v & (t0 ^ t1))
+            if g.is_true() {
+                UExpr::xor(v.clone(), e0.clone())
+            } else {
+                UExpr::xor(e0.clone(), UExpr::and(v.clone(), syn(&g)))
+            },
+            UExpr::or(
+                UExpr::and(v.clone(), e1.clone()),
+                UExpr::and(UExpr::not(v.clone()), e0.clone())
+            ),
+        ];
+
+        // If one cofactor implies the other,
+        // then it doesn't need to be restricted.
+        if t0.zip(&t1, |a, b| !a || b).is_true() {
+            candidates.push(UExpr::or(e0.clone(), UExpr::and(v.clone(), e1.clone())));
+        }
+        if t1.zip(&t0, |a, b| !a || b).is_true() {
+            candidates.push(UExpr::or(e1, UExpr::and(UExpr::not(v), e0)));
+        }
+
+        candidates.into_iter().min_by_key(cost).unwrap()
+    };
+
+    memo.insert(t.clone(), e.clone());
+    e
+}
+
+/// The number of operators in the expression.
+pub fn cost(e: &UExpr) -> usize {
+    match e {
+        UExpr::Ones | UExpr::Var(_) => 0,
+        UExpr::Not(i) => 1 + cost(i),
+        UExpr::And(l, r) | UExpr::Or(l, r) | UExpr::Xor(l, r) => 1 + cost(l) + cost(r),
+    }
+}
+
+/// Replaces the placeholder variables "0", "1", ... by the names.
+fn instantiate(e: &UExpr, vars: &[String]) -> UExpr {
+    match e {
+        UExpr::Ones => UExpr::Ones,
+        UExpr::Var(i) => UExpr::Var(vars[i.parse::<usize>().unwrap()].clone()),
+        UExpr::Not(i) => UExpr::not(instantiate(i, vars)),
+        UExpr::And(l, r) => UExpr::and(instantiate(l, vars), instantiate(r, vars)),
+        UExpr::Or(l, r) => UExpr::or(instantiate(l, vars), instantiate(r, vars)),
+        UExpr::Xor(l, r) => UExpr::xor(instantiate(l, vars), instantiate(r, vars)),
+    }
+}
+
+/// Returns the smallest expressions for all truth tables over `vars`
+/// variables, indexed by the truth table as a number.
+/// The variables are called "0", "1", ...
+fn exact(vars: usize) -> &'static [UExpr] {
+    static TABLES: [OnceLock<Vec<UExpr>>; EXACT_VARS + 1] = [
+        OnceLock::new(), OnceLock::new(), OnceLock::new(), OnceLock::new()
+    ];
+
+    TABLES[vars].get_or_init(|| {
+        let rows = 1 << vars;
+        let all = (1usize << rows) - 1;
+        let mut best: Vec<Option<UExpr>> = vec![None; all + 1];
+
+        // Remembers the expression for a truth table if there is none yet.
+        fn add(
+            best: &mut [Option<UExpr>], level: &mut Vec<usize>,
+            t: usize, e: impl FnOnce(&[Option<UExpr>]) -> UExpr
+        ) {
+            if best[t].is_none() {
+                best[t] = Some(e(best));
+                level.push(t);
+            }
+        }
+
+        // levels[c] contains the truth tables whose
+        // smallest expression has c operators.
+        let mut level = Vec::new();
+        add(&mut best, &mut level, all, |_| UExpr::Ones);
+        for v in 0..vars {
+            let t = (0..rows).filter(|i| (i >> v) & 1 == 1)
+                .fold(0, |t, i| t | 1 << i);
+            add(&mut best, &mut level, t, |_| UExpr::Var(v.to_string()));
+        }
+        let mut levels = vec![level];
+
+        // Ones, the variables and the operators are complete,
+        // so this terminates.
+        while best.iter().any(Option::is_none) {
+            let c = levels.len();
+            let mut level = Vec::new();
+
+            for &t in &levels[c - 1] {
+                add(&mut best, &mut level, !t & all,
+                    |b| UExpr::not(b[t].clone().unwrap()));
+            }
+
+            for ca in 0..c {
+                let cb = c - 1 - ca;
+                for &l in &levels[ca] {
+                    for &r in &levels[cb] {
+                        let e = |b: &[Option<UExpr>]| (
+                            b[l].clone().unwrap(), b[r].clone().unwrap()
+                        );
+                        add(&mut best, &mut level, l & r,
+                            |b| { let (l, r) = e(b); UExpr::and(l, r) });
+                        add(&mut best, &mut level, l | r,
+                            |b| { let (l, r) = e(b); UExpr::or(l, r) });
+                        add(&mut best, &mut level, l ^ r,
+                            |b| { let (l, r) = e(b); UExpr::xor(l, r) });
+                    }
+                }
+            }
+
+            levels.push(level);
+        }
+
+        best.into_iter().map(Option::unwrap).collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+    use super::*;
+
+    fn vars(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("x{}", i)).collect()
+    }
+
+    /// Synthesizes the expression and checks that it has the truth table.
+    fn check(t: &TruthTable) -> UExpr {
+        let vars = vars(t.num_vars());
+        let e = synthesize(t, &vars);
+        assert_eq!(TruthTable::of_uexpr(&e, &vars), *t, "Wrong expression {}", e);
+        e
+    }
+
+    #[test]
+    fn all_small_functions() {
+        for n in 1..=3 {
+            let rows = 1 << n;
+            for m in 0..1usize << rows {
+                check(&TruthTable::from_fn(n, |i| (m >> i) & 1 == 1));
+            }
+        }
+    }
+
+    #[test]
+    fn sampled_functions() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for n in 4..=6 {
+            for _ in 0..256 {
+                check(&TruthTable::from_fn(n, |_| rng.gen()));
+            }
+        }
+    }
+
+    #[test]
+    fn minimal_size() {
+        let bit = |i: usize, j: usize| (i >> j) & 1 == 1;
+        let xor = TruthTable::from_fn(2, |i| bit(i, 0) ^ bit(i, 1));
+        assert_eq!(cost(&check(&xor)), 1);
+
+        let xnor = TruthTable::from_fn(2, |i| !(bit(i, 0) ^ bit(i, 1)));
+        assert_eq!(cost(&check(&xnor)), 2);
+
+        let maj = TruthTable::from_fn(3, |i| (0..3).filter(|&j| bit(i, j)).count() >= 2);
+        assert_eq!(cost(&check(&maj)), 4);
+
+        let mux = TruthTable::from_fn(3, |i| if bit(i, 2) { bit(i, 1) } else { bit(i, 0) });
+        assert_eq!(cost(&check(&mux)), 3);
+
+        // The decomposition finds the parity of four variables.
+        let parity = TruthTable::from_fn(4, |i| i.count_ones() % 2 == 1);
+        assert_eq!(cost(&check(&parity)), 3);
+    }
+}
@@ -5,7 +5,8 @@ use std::rc::Rc;
 use num_traits::Num;
 
 use crate::expr::Expr;
-use crate::numbers::{UnsignedInt, UniformNum, int_from_it};
+use crate::numbers::{UnsignedInt, UniformNum, int_from_str};
+use crate::parser::{Lexer, Token, ParseError};
 
 /// LUExpr is short for "Linear combination of Uniform Expressions"
 /// These are the expressions for which rewrite rules can be efficiently
@@ -55,35 +56,34 @@ impl<T: UniformNum> LUExpr<T> {
     /// and expects very specific syntax.
     /// It is used for convenience when testing things and
     /// not really meant to be used by something outside this crate.
-    pub(crate) fn from_string(s: String) -> Option<Self> {
-        let mut s = s.to_string();
-        s.retain(|c| !c.is_whitespace());
-        let mut it = s.chars().peekable();
+    pub(crate) fn from_string<U: AsRef<str>>(s: U) -> Result<Self, ParseError> {
+        let mut l = Lexer::new(s.as_ref());
 
         // This stores the current linear combination.
         let mut v = Vec::new();
 
+        // The empty linear combination is zero.
+        if l.peek().0 == Token::Eof {
+            return Ok(Self(v));
+        }
+
         let mut neg = false;
 
         // Loop over the string/the summands.
         loop {
-            // Are there still characters left?
-            // If not then we're done.
-            let mut c = match it.peek() {
-                None => return Some(Self(v)),
-                Some(c) => *c,
-            };
-
-            if c == '-' {
+            if l.peek().0 == Token::Sym("-") {
                 neg = true;
-                it.next();
-                c = *it.peek()?;
+                l.next_token();
             }
 
-            // If this is a digit then we expect num*UExpr.
-            if c.is_ascii_digit() {
+            // If this is a number then we expect num*UExpr.
+            if let (Token::Num(n), span) = l.peek() {
+                l.next_token();
+
                 // Parse the number.
-                let mut num = int_from_it(&mut it)?;
+                let mut num = int_from_str::<T>(n).map_err(|e|
+                    ParseError::new(Token::Num(n), span, &[e])
+                )?;
 
                 // If the number is negative then negate it.
                 if neg {
@@ -91,23 +91,21 @@ impl<T: UniformNum> LUExpr<T> {
                 }
 
                 // Is it the expected '*'?
-                match it.peek() {
-                    Some('*') => {
-                        it.next();
-
-                        // Parse the UExpr.
-                        let e = UExpr::parse(&mut it, 0)?;
+                if l.peek().0 == Token::Sym("*") {
+                    l.next_token();
 
-                        // Push it.
-                        v.push((num, e));
-                    },
+                    // Parse the UExpr.
+                    let e = UExpr::parse(&mut l, 0)?;
 
-                    // If this is a different character then we push -num*(-1).
-                    _ => v.push((T::zero() - num, UExpr::Ones)),
+                    // Push it.
+                    v.push((num, e));
+                } else {
+                    // Otherwise we push -num*(-1).
+                    v.push((T::zero() - num, UExpr::Ones));
                 }
             } else {
                 // We don't have a factor so just parse the UExpr.
-                let e = UExpr::parse(&mut it, 0)?;
+                let e = UExpr::parse(&mut l, 0)?;
 
                 let sign = match neg {
                     false => T::one(),
@@ -118,17 +116,19 @@ impl<T: UniformNum> LUExpr<T> {
                 v.push((sign, e));
             }
 
-            // If the next character is not a plus then we are done.
-            match it.peek() {
+            // If the next token is not a plus or minus then we are done.
+            match l.next_token() {
                 // Next part of the linear combination.
-                Some('+') => { neg = false; it.next() }, // Skip the +.
-                Some('-') => { neg = true; it.next() },
+                (Token::Sym("+"), _) => neg = false,
+                (Token::Sym("-"), _) => neg = true,
 
                 // We consumed the whole input so we're good.
-                None => return Some(Self(v)),
+                (Token::Eof, _) => return Ok(Self(v)),
 
                 // There is something left but we can't parse it.
-                _ => return None,
+                (t, span) => return Err(ParseError::new(
+                    t, span, &["operator", "end of input"]
+                )),
             };
         }
     }
@@ -145,7 +145,9 @@ impl<T: UniformNum> LUExpr<T> {
 
         // Lambda to convert the `coefficient * uexpr` into an expr.
         let term = |f: T, u: &UExpr| {
-            if f.is_one() {
+            if *u == UExpr::Ones {
+                Expr::Const(T::zero() - f)
+            } else if f.is_one() {
                 u.to_expr()
             } else {
                 Expr::Mul(Rc::new(Expr::Const(f)), Rc::new(u.to_expr()))
@@ -229,6 +231,7 @@ impl UExpr {
         Self::Var(c)
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn not(e: Self) -> Self {
         Self::Not(e.into())
     }
@@ -248,10 +251,7 @@ impl UExpr {
     /// Is the top-most operator unary.
     pub fn is_unary(&self) -> bool {
         use UExpr::*;
-        match self {
-            Ones | Var(_) | Not(_) => true,
-            _ => false,
-        }
+        matches!(self, Ones | Var(_) | Not(_))
     }
 
     /// Returns all variables in the expression.
@@ -318,90 +318,57 @@ impl UExpr {
     }
 
     /// Parse a string to an expression.
-    pub(crate) fn from_string<T: ToString>(s: T) -> Option<Self> {
-        let mut s = s.to_string();
-        s.retain(|c| !c.is_whitespace());
-        let mut it = s.chars().peekable();
-
-        Self::parse(&mut it, 0)
-            .filter(|_| it.next().is_none())
+    pub(crate) fn from_string<T: AsRef<str>>(s: T) -> Result<Self, ParseError> {
+        let mut l = Lexer::new(s.as_ref());
+        let e = Self::parse(&mut l, 0)?;
+        l.expect_eof(&["operator", "end of input"])?;
+        Ok(e)
     }
 
-    pub(self) fn parse(
-        it: &mut std::iter::Peekable<std::str::Chars>,
-        pre: usize
-    ) -> Option<Self> {
+    pub(self) fn parse(l: &mut Lexer, pre: usize) -> Result<Self, ParseError> {
         use UExpr::*;
 
-        let c = *it.peek()?;
-
-        let mut e = if c == '(' {
-            it.next();
-            let e = Self::parse(it, 0)?;
-            match it.next() {
-                Some(')') => e,
-                _ => return None,
-            }
-        } else if c == '~' || c == '!' {
-            it.next();
-            let e = Self::parse(it, 15)?;
-            Not(Box::new(e))
-        } else if c.is_alphabetic() {
-            it.next();
-            let mut var = String::from(c);
-            loop {
-                let Some(c) = it.peek() else {
-                    break
-                };
-
-                if !c.is_alphanumeric() {
-                    break
-                }
-
-                var.push(*c);
-                it.next();
-            }
-            
-            Var(var)
-        } else if c == '-' {
-            it.next();
+        let mut e = match l.next_token() {
+            (Token::Sym("("), _) => {
+                let e = Self::parse(l, 0)?;
+                l.expect(")", &["operator", "')'"])?;
+                e
+            },
+            (Token::Sym("~" | "!"), _) => Not(Box::new(Self::parse(l, 15)?)),
+            (Token::Ident(v), _) => Var(v.to_owned()),
             // Parse a -1.
-            match it.next() {
-                Some('1') => Ones,
-                _ => return None,
-            }
-        } else {
-            return None;
+            (Token::Sym("-"), _) => match l.next_token() {
+                (Token::Num("1"), _) => Ones,
+                (t, span) => return Err(ParseError::new(t, span, &["'1'"])),
+            },
+            (t, span) => return Err(ParseError::new(
+                t, span, &["variable", "'-1'", "'('", "'~'", "'!'"]
+            )),
         };
 
         loop {
-            let c = match it.peek() {
-                None => return Some(e),
-                Some(c) => *c,
-            };
-
-            let op_pre = match c {
-                '|' => 1,
-                '^' => 2,
-                '&' => 3,
-                _ => return Some(e),
+            let op_pre = match l.peek().0 {
+                Token::Sym("|") => 1,
+                Token::Sym("^") => 2,
+                Token::Sym("&") => 3,
+                _ => return Ok(e),
             };
 
             if op_pre <= pre {
-                return Some(e);
+                return Ok(e);
             }
 
             // If the current operators precedence is higher than
             // the one whose subexpressions we are currently parsing
             // then we need to finish this operator first.
-            it.next();
-            let rhs = Box::new(Self::parse(it, op_pre)?);
+            let (op, _) = l.next_token();
+            let rhs = Box::new(Self::parse(l, op_pre)?);
             let lhs = Box::new(e);
-            e = match c {
-                '&' => And(lhs, rhs),
-                '|' => Or(lhs, rhs),
-                '^' => Xor(lhs, rhs),
-                _ => return None,
+            e = match op {
+                Token::Sym("&") => And(lhs, rhs),
+                Token::Sym("|") => Or(lhs, rhs),
+                Token::Sym("^") => Xor(lhs, rhs),
+                _ => unreachable!(),
             };
         }
     }
@@ -438,7 +405,7 @@ impl std::fmt::Display for UExpr {
 }
 
 /// Stores values that should be substituted into variables.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Valuation<T> {
     /// The key value pairs are stored as a Vector
     /// because I doubt a hashmap/tree would be faster
@@ -458,6 +425,13 @@ impl<T: Num> Valuation<T> {
     }
 }
 
+impl<T> Valuation<T> {
+    /// Returns the variables and their values.
+    pub fn iter(&self) -> std::slice::Iter<'_, (String, T)> {
+        self.vals.iter()
+    }
+}
+
 impl<T> Index<&str> for Valuation<T> {
     type Output = T;
     fn index(&self, index: &str) -> &Self::Output {
@@ -150,6 +150,12 @@ impl<T: UnsignedInt> Vector<T> {
     }
 }
 
+impl<T: PartialEq> PartialEq for Vector<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.dim == other.dim && self.entries == other.entries
+    }
+}
+
 impl<T> Index<usize> for Vector<T> {
     type Output = T;
 
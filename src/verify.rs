@@ -0,0 +1,203 @@
+//! Checks that two expressions compute the same function.
+
+use std::fmt::{self, Display, Formatter};
+use rand::Rng;
+use rand::distributions::{Standard, Distribution};
+use crate::expr::{Expr, sort_args};
+use crate::numbers::UniformNum;
+use crate::uniform_expr::Valuation;
+
+/// If there are at most this many input bits,
+/// all valuations are checked.
+const EXHAUSTIVE_BITS: u32 = 16;
+
+/// The number of random valuations that are checked otherwise.
+const RANDOM_SAMPLES: usize = 1024;
+
+/// The maximum number of valuations in the grid of corner cases.
+const MAX_CORNER_GRID: usize = 4096;
+
+/// A valuation for which two expressions differ.
+#[derive(Debug)]
+pub struct Counterexample<T> {
+    /// The values of the variables.
+    pub valuation: Valuation<T>,
+
+    /// The value of the first expression.
+    pub expected: T,
+
+    /// The value of the second expression.
+    pub found: T,
+}
+
+impl<T: UniformNum> Display for Counterexample<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, (var, val)) in self.valuation.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} = {}", var, val)?;
+        }
+        write!(f, ": expected {}, found {}", self.expected, self.found)
+    }
+}
+
+/// Checks that `expected` and `found` agree on all inputs.
+/// `vars` has to contain all variables of both expressions.
+/// `signed` determines how they are evaluated, see [`Expr::eval`].
+///
+/// If the inputs have at most 16 bits in total, all of them are tried.
+/// Otherwise the expressions are compared on the corner cases
+/// (0, 1, 2, -1, -2 and the largest and smallest signed numbers)
+/// and on random valuations, so a mismatch may go unnoticed.
+pub fn verify<T, R>(
+    expected: &Expr<T>, found: &Expr<T>, vars: &[String], signed: bool, rng: &mut R
+) -> Result<(), Counterexample<T>>
+    where
+        T: UniformNum,
+        R: Rng,
+        Standard: Distribution<T>,
+{
+    let mut val = Valuation::zero(vars.to_vec());
+    let mut check = |val: &Valuation<T>| {
+        let e = expected.eval(val, signed);
+        let f = found.eval(val, signed);
+        if e == f {
+            Ok(())
+        } else {
+            Err(Counterexample { valuation: val.clone(), expected: e, found: f })
+        }
+    };
+
+    let bits = T::bits() as usize * vars.len();
+    if bits <= EXHAUSTIVE_BITS as usize {
+        for i in 0..1usize << bits {
+            for (j, v) in vars.iter().enumerate() {
+                let x = i >> (j * T::bits() as usize) & ((1 << T::bits()) - 1);
+                val[v] = T::from_u128(x as u128);
+            }
+            check(&val)?;
+        }
+        return Ok(());
+    }
+
+    let min = T::one() << (T::bits() as usize - 1);
+    let corners = [
+        T::zero(), T::one(), T::one() + T::one(),
+        T::zero() - T::one(), T::zero() - T::one() - T::one(),
+        min, min - T::one(),
+    ];
+
+    // Try all combinations of corner cases if there aren't too many.
+    let grid = corners.len().checked_pow(vars.len() as u32)
+        .filter(|g| *g <= MAX_CORNER_GRID);
+    if let Some(grid) = grid {
+        for mut i in 0..grid {
+            for v in vars {
+                val[v] = corners[i % corners.len()];
+                i /= corners.len();
+            }
+            check(&val)?;
+        }
+    }
+
+    // Random valuations where some variables are corner cases.
+    for _ in 0..RANDOM_SAMPLES {
+        for v in vars {
+            val[v] = if rng.gen_ratio(1, 4) {
+                corners[rng.gen_range(0..corners.len() as u32) as usize]
+            } else {
+                rng.gen()
+            };
+        }
+        check(&val)?;
+    }
+
+    Ok(())
+}
+
+/// Returns an SMT-LIB script that asks a solver for an input
+/// on which the expressions differ.
+/// If the solver answers `unsat`, then they are equivalent.
+/// The functions are called `f.original` and `f.obfuscated`,
+/// so they don't clash with the inputs.
+pub fn smt_query<T: UniformNum>(
+    original: &Expr<T>, obfuscated: &Expr<T>, signed: bool
+) -> String {
+    let mut vars = original.vars();
+    vars.extend(obfuscated.vars());
+    vars.sort();
+    vars.dedup();
+    sort_args(&mut vars);
+
+    let mut s = String::from("(set-logic QF_BV)\n");
+    for v in &vars {
+        s += &format!("(declare-const {} (_ BitVec {}))\n", v, T::bits());
+    }
+    s += &original.print_smt_fn("f.original", &vars, signed);
+    s += "\n";
+    s += &obfuscated.print_smt_fn("f.obfuscated", &vars, signed);
+    s += "\n";
+
+    // Functions without arguments are used without parentheses.
+    let call = |f: &str| if vars.is_empty() {
+        f.to_owned()
+    } else {
+        format!("({} {})", f, vars.join(" "))
+    };
+    s += &format!("(assert (not (= {} {})))\n", call("f.original"), call("f.obfuscated"));
+    s += "(check-sat)";
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::Wrapping;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use crate::obfuscate::ObfuscationError;
+    use super::*;
+
+    fn check<T: UniformNum>(expected: &str, found: &str) -> Result<(), Counterexample<T>>
+        where Standard: Distribution<T>
+    {
+        let e = Expr::from_string(expected).unwrap();
+        let f = Expr::from_string(found).unwrap();
+        let vars = ["x".to_owned(), "y".to_owned()];
+        verify(&e, &f, &vars, false, &mut ChaCha8Rng::seed_from_u64(0))
+    }
+
+    /// With 16 input bits, every input is tried,
+    /// so a difference on a single input is found.
+    #[test]
+    fn exhaustive() {
+        type W = Wrapping<u8>;
+        assert!(check::<W>("x + y", "(x ^ y) + 2 * (x & y)").is_ok());
+
+        // The added term is 1 iff x = 173 and y = 94.
+        let c = check::<W>("x * y", "x * y + ((x ^ 173 | y ^ 94) - 1) / 255").unwrap_err();
+        assert_eq!(c.to_string(), "x = 173, y = 94: expected 134, found 135");
+    }
+
+    #[test]
+    fn sampled() {
+        type W = Wrapping<u32>;
+        assert!(check::<W>("x + y", "(x ^ y) + 2 * (x & y)").is_ok());
+        assert!(check::<W>("x * y", "(x | y) * (x & y) + (x & ~y) * (~x & y)").is_ok());
+        assert!(check::<W>("x + y", "x - y").is_err());
+
+        // Only wrong for the smallest signed number, which is a corner case.
+        let c = check::<W>("x - y", "x - y + (x - 2147483649) / 4294967295").unwrap_err();
+        assert_eq!(c.valuation["x"], Wrapping(1 << 31));
+        assert_eq!(c.found, c.expected + Wrapping(1));
+    }
+
+    #[test]
+    fn mismatch_error() {
+        let c = check::<Wrapping<u8>>("x & y", "x | y").unwrap_err();
+        let e = ObfuscationError::from(c);
+        assert!(matches!(&e, ObfuscationError::Mismatch { valuation, expected: 0, found: 1 }
+            if *valuation == [("x".to_owned(), 1), ("y".to_owned(), 0)]));
+        assert_eq!(e.to_string(), "The obfuscated expression is wrong for x = 1, y = 0: expected 0, found 1");
+    }
+}
@@ -0,0 +1,86 @@
+//! Runs the `mba` binary and checks its output and exit status.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the binary with the arguments and `stdin` as the input.
+fn mba(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mba"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run mba");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Runs the binary and returns stdout, which has to be successful.
+fn ok(args: &[&str]) -> String {
+    let out = mba(args, "");
+    assert!(out.status.success(), "mba {:?} failed:\n{}",
+        args, String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+/// Runs the binary and returns stderr, which has to fail with the code.
+fn err(args: &[&str], code: i32) -> String {
+    let out = mba(args, "");
+    assert_eq!(out.status.code(), Some(code), "mba {:?}", args);
+    assert!(out.stdout.is_empty());
+    String::from_utf8(out.stderr).unwrap()
+}
+
+#[test]
+fn help() {
+    assert!(ok(&["--help"]).starts_with("Usage: mba [OPTIONS] [EXPR]"));
+    assert!(ok(&["-w", "16", "-h"]).starts_with("Usage: mba"));
+}
+
+#[test]
+fn simplify() {
+    assert_eq!(ok(&["-S", "-p", "default", "(x ^ y) + 2 * (x & y)"]), "x + y\n");
+    assert_eq!(ok(&["--simplify", "--printer=default", "--", "-x - ~x"]), "1\n");
+    assert_eq!(ok(&["-S", "-p", "rust", "-w", "16", "x + y - (x & y)"]),
+        "fn f(x: Wrapping<u16>, y: Wrapping<u16>) -> Wrapping<u16> {\n\tx | y\n}\n");
+}
+
+#[test]
+fn stdin() {
+    let out = mba(&["-S", "-p", "default", "-w", "3", "-"], "(x | y) - (x & y)\n");
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "x ^ y\n");
+
+    let out = mba(&["-S", "-p", "default"], "x - (x & y)");
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "x & ~y\n");
+}
+
+#[test]
+fn obfuscate() {
+    let args = ["-s", "5", "-w", "32", "-p", "python", "-c", "4", "x + y"];
+    let out = ok(&args);
+    assert!(out.starts_with("def f(x, y):\n"), "{}", out);
+    assert!(out.contains("& 0xffffffff"), "{}", out);
+    assert_eq!(ok(&args), out, "The same seed gave different outputs");
+
+    let out = ok(&["--seed=5", "--width=64", "--signed", "-a", "1", "x * y"]);
+    assert!(out.starts_with("int64_t f("), "{}", out);
+}
+
+#[test]
+fn errors() {
+    // Invalid arguments exit with 2 and print the usage.
+    let e = err(&["--frobnicate", "x"], 2);
+    assert!(e.starts_with("error: Unknown option '--frobnicate'\n\nUsage: mba"), "{}", e);
+    let e = err(&["-w", "0", "x"], 2);
+    assert!(e.starts_with("error: The width has to be between 1 and 128 bits, not 0."), "{}", e);
+    assert!(err(&["-p", "cobol", "x"], 2).starts_with("error: Invalid printer 'cobol'"));
+    assert!(err(&["x", "-w"], 2).starts_with("error: Missing value for '-w'"));
+    assert!(err(&["x", "y"], 2).starts_with("error: Unexpected argument 'y'"));
+
+    // Invalid expressions exit with 1 and underline the error.
+    assert_eq!(err(&["-S", "x + * y"], 1),
+        "error: Expected variable, constant, '(', '~' or '-', found '*'\n  x + * y\n      ^\n");
+    assert_eq!(err(&["-S", "x * y"], 1), "error: The expression is not a linear MBA expression.\n");
+}
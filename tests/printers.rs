@@ -0,0 +1,499 @@
+//! Compares the functions that the printers produce with [`Expr::eval`]
+//! by running them with `gcc`, `rustc`, `python3`, `node` and `lli`.
+//! The languages whose tools aren't installed are skipped.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::rc::Rc;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use mba_wasm::{Block, Expr, Printer, UInt, UniformNum, Valuation};
+
+/// The widths that don't have a primitive type, and two that have.
+/// JavaScript uses numbers up to 32 bits.
+const WIDTHS: [u32; 6] = [1, 8, 12, 24, 32, 48];
+
+/// The widths of C's standard types and `__int128`.
+/// The installed compilers may not support `_BitInt` yet.
+const C_WIDTHS: [u32; 5] = [8, 16, 32, 64, 128];
+
+/// The expressions that are checked.
+/// The constants fit into every width.
+const EXPRS: &[&str] = &[
+    "x * y + 1 - (x ^ y)",
+    "~x * -y + (x & y) * (x | ~y)",
+    "-(x - y) * (x + y) ^ ~(x * x)",
+    "x / (y | 1) + x % (y | 1) * y",
+    "(x * y) >> 1 ^ (x - y) << 1",
+];
+
+/// The expressions with operations that are undefined in some languages
+/// for some inputs, like division by zero.
+const UNDEFINED: &[&str] = &[
+    "x / y",
+    "x % y",
+    "x << y",
+    "x >> y",
+    "(x - y) / (x + y) + (x ^ y) % (x & y)",
+    "(x * y) << (x - y) ^ (x | y) >> (y + 1)",
+];
+
+/// The number of random inputs for each expression.
+const INPUTS: usize = 32;
+
+/// The expressions at the current width of `UInt`,
+/// including the ones in [`UNDEFINED`] if `undefined` is set.
+/// The third to last one has a large constant, which is negative
+/// for signed integers, the second to last one has the minimum
+/// of the signed integers and the last one shares a subexpression,
+/// so the printers use variables.
+fn exprs(undefined: bool) -> Vec<Expr<UInt>> {
+    let undefined = if undefined { UNDEFINED } else { &[] };
+    let mut exprs: Vec<_> = EXPRS.iter().chain(undefined)
+        .map(|s| Expr::from_string(*s).unwrap())
+        .collect();
+
+    let c = UInt::mask() / 3 * 2;
+    exprs.push(Expr::from_string(format!("x + {} * y - {}", c, c)).unwrap());
+    let min = 1u128 << (UInt::bits() - 1);
+    exprs.push(Expr::from_string(format!("x * {} + {} - y", min, min)).unwrap());
+
+    let mut shared = Expr::from_string("z * z - (z >> 1) + z / (x | 1)").unwrap();
+    let mut z = Rc::new(Expr::from_string("x * y - ~x").unwrap());
+    shared.substitute(&mut z, "z");
+    exprs.push(shared);
+    exprs
+}
+
+/// The inputs (x, y), including the edge cases
+/// and shift amounts around the width.
+fn inputs() -> Vec<(UInt, UInt)> {
+    let n = |v: u128| UInt::new(v);
+    let (m, h, bits) = (n(UInt::mask()), n(UInt::mask() >> 1), UInt::bits() as u128);
+    let mut v = vec![
+        (n(0), m), (m, m), (h, m), (m, h), (h + n(1), h),
+        (m, n(0)), (h + n(1), n(0)), (h + n(1), m), (n(0), n(0)),
+        (m, n(bits - 1)), (h + n(1), n(bits)), (m, n(bits + 1)),
+    ];
+    let mut rng = ChaCha8Rng::seed_from_u64(UInt::bits() as u64);
+    v.extend((0..INPUTS).map(|_| (rng.gen(), rng.gen())));
+    v.extend((0..INPUTS).map(|_| (rng.gen(), n(rng.gen_range(0..=bits + 1)))));
+    v
+}
+
+/// The results of `eval`, one per line, in the order of the calls.
+fn expected(exprs: &[Expr<UInt>], inputs: &[(UInt, UInt)], signed: bool) -> String {
+    let mut s = String::new();
+    for e in exprs {
+        for &(x, y) in inputs {
+            let mut v = Valuation::zero(vec!["x".to_owned(), "y".to_owned()]);
+            v["x"] = x;
+            v["y"] = y;
+            let r = e.eval(&v, signed);
+            if signed {
+                writeln!(&mut s, "{}", r.to_i128()).unwrap();
+            } else {
+                writeln!(&mut s, "{}", r).unwrap();
+            }
+        }
+    }
+    s
+}
+
+/// Prints the function for the expression under a unique name.
+/// The functions always take both x and y, so unused ones are added.
+fn function(e: &Expr<UInt>, i: usize, printer: Printer, signed: bool) -> String {
+    let f = e.print_as_fn(printer, signed);
+    let (keyword, args) = match printer {
+        Printer::C => (" f(", e.vars()),
+        Printer::Rust => ("fn f(", e.vars()),
+        Printer::Python => ("def f(", e.vars()),
+        Printer::JavaScript => ("function f(", e.vars()),
+        Printer::LlvmIr => ("@f(", e.vars()),
+        _ => unreachable!(),
+    };
+    assert_eq!(args.len(), 2, "The expressions use x and y.");
+    f.replacen(keyword, &keyword.replace("f(", &format!("f{}(", i)), 1)
+}
+
+/// Formats an argument of a call.
+fn arg(v: UInt, printer: Printer, signed: bool) -> String {
+    let v = if signed && printer != Printer::C { v.to_i128().to_string() } else { v.to_string() };
+    match printer {
+        // The unsigned integer is converted to the signed one.
+        // C has no 128-bit literals.
+        Printer::C if v.len() > 19 => {
+            let v = v.parse::<u128>().unwrap();
+            format!("(((unsigned __int128){}u << 64) | {}u)", v >> 64, v as u64)
+        },
+        Printer::C => format!("{}u", v),
+        Printer::Rust => format!("Wrapping({})", v),
+        Printer::JavaScript if UInt::bits() > 32 => format!("{}n", v),
+        Printer::LlvmIr => format!("i{} {}", UInt::bits(), v),
+        _ => v,
+    }
+}
+
+/// The program that prints the result of every call on its own line.
+fn program(
+    exprs: &[Expr<UInt>], inputs: &[(UInt, UInt)], printer: Printer, signed: bool
+) -> String {
+    let mut s = String::new();
+    match printer {
+        // printf can't print 128-bit integers.
+        Printer::C => s += "#include <stdint.h>\n#include <stdio.h>\n\n\
+            void print_u(unsigned __int128 v) {\n\
+            \tchar buf[40], *p = buf + 39;\n\
+            \t*p = 0;\n\
+            \tdo *--p = '0' + v % 10; while (v /= 10);\n\
+            \tputs(p);\n\
+            }\n\n\
+            void print_i(__int128 v) {\n\
+            \tif (v < 0) putchar('-');\n\
+            \tprint_u(v < 0 ? -(unsigned __int128)v : (unsigned __int128)v);\n\
+            }\n\n",
+        Printer::Rust => s += "#![allow(unused_parens, clippy::all)]\nuse std::num::Wrapping;\n",
+        Printer::LlvmIr => {
+            s += "declare i32 @printf(i8*, ...)\n";
+            s += "@fmt = private constant [6 x i8] c\"%lld\\0A\\00\"\n";
+        },
+        _ => {},
+    }
+    for (i, e) in exprs.iter().enumerate() {
+        writeln!(&mut s, "{}", function(e, i, printer, signed)).unwrap();
+    }
+    match printer {
+        Printer::C => s += "int main(void) {\n",
+        Printer::Rust => s += "fn main() {\n",
+        Printer::LlvmIr => s += "define i32 @main() {\n",
+        _ => {},
+    }
+    let mut n = 0;
+    for i in 0..exprs.len() {
+        for &(x, y) in inputs {
+            let call = format!("f{}({}, {})", i,
+                arg(x, printer, signed), arg(y, printer, signed));
+            match printer {
+                Printer::C if signed => writeln!(&mut s, "\tprint_i({});", call),
+                Printer::C => writeln!(&mut s, "\tprint_u({});", call),
+                Printer::Rust => writeln!(&mut s, "\tprintln!(\"{{}}\", {}.0);", call),
+                Printer::Python => writeln!(&mut s, "print({})", call),
+                Printer::JavaScript => writeln!(&mut s, "console.log(String({}));", call),
+                Printer::LlvmIr => {
+                    // The results are printed as 64-bit integers.
+                    let ext = if signed { "sext" } else { "zext" };
+                    write!(&mut s, "\t%r{n} = call i{b} @{call}\n\
+                        \t%e{n} = {ext} i{b} %r{n} to i64\n\
+                        \tcall i32 (i8*, ...) @printf(i8* getelementptr inbounds \
+                        ([6 x i8], [6 x i8]* @fmt, i64 0, i64 0), i64 %e{n})\n",
+                        b = UInt::bits())
+                },
+                _ => unreachable!(),
+            }.unwrap();
+            n += 1;
+        }
+    }
+    match printer {
+        Printer::C => s += "\treturn 0;\n}\n",
+        Printer::Rust => s += "}\n",
+        Printer::LlvmIr => s += "\tret i32 0\n}\n",
+        _ => {},
+    }
+    s
+}
+
+/// Runs the program and returns its output,
+/// or None if the tool isn't installed.
+fn run(src: &str, printer: Printer, name: &str) -> Option<String> {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let output = match printer {
+        // Undefined behavior makes the program fail.
+        Printer::C => {
+            let path = dir.join(format!("{}.c", name));
+            let exe = dir.join(name);
+            std::fs::write(&path, src).unwrap();
+            let out = Command::new("gcc")
+                .args(["-std=c11", "-O2", "-Wall", "-Werror", "-Wno-parentheses",
+                    "-fsanitize=undefined", "-fno-sanitize-recover=all", "-o"])
+                .arg(&exe).arg(&path)
+                .output().ok()?;
+            assert!(out.status.success(), "gcc failed:\n{}\n{}",
+                String::from_utf8_lossy(&out.stderr), src);
+            Command::new(&exe).output().unwrap()
+        },
+        Printer::Rust => {
+            let path = dir.join(format!("{}.rs", name));
+            let exe = dir.join(name);
+            std::fs::write(&path, src).unwrap();
+            let out = Command::new("rustc")
+                .args(["--edition", "2021", "-O", "-o"])
+                .arg(&exe).arg(&path)
+                .output().ok()?;
+            assert!(out.status.success(), "rustc failed:\n{}\n{}",
+                String::from_utf8_lossy(&out.stderr), src);
+            Command::new(&exe).output().unwrap()
+        },
+        Printer::Python => {
+            let path = dir.join(format!("{}.py", name));
+            std::fs::write(&path, src).unwrap();
+            Command::new("python3").arg(&path).output().ok()?
+        },
+        Printer::JavaScript => {
+            let path = dir.join(format!("{}.js", name));
+            std::fs::write(&path, src).unwrap();
+            Command::new("node").arg(&path).output().ok()?
+        },
+        Printer::LlvmIr => {
+            let path = dir.join(format!("{}.ll", name));
+            std::fs::write(&path, src).unwrap();
+            Command::new("lli").arg(&path).output().ok()?
+        },
+        _ => unreachable!(),
+    };
+
+    assert!(output.status.success(), "The program failed:\n{}\n{}",
+        String::from_utf8_lossy(&output.stderr), src);
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+/// Checks the printer against `eval` for all widths,
+/// including the [`UNDEFINED`] expressions if `undefined` is set.
+fn check(printer: Printer, signed: bool, undefined: bool) {
+    let widths = if printer == Printer::C { &C_WIDTHS[..] } else { &WIDTHS };
+    for &bits in widths {
+        UInt::with_width(bits, || {
+            let exprs = exprs(undefined);
+            let inputs = inputs();
+            let src = program(&exprs, &inputs, printer, signed);
+            let name = format!("{:?}_{}_{}", printer, bits, signed).to_lowercase();
+            let Some(out) = run(&src, printer, &name) else {
+                eprintln!("Skipping {:?}, because the tool isn't installed.", printer);
+                return;
+            };
+
+            // Find the first call whose result differs.
+            let expected = expected(&exprs, &inputs, signed);
+            for (i, (o, e)) in out.lines().zip(expected.lines()).enumerate() {
+                let (x, y) = inputs[i % inputs.len()];
+                let expr = exprs[i / inputs.len()].print_as_fn(Printer::Default, false);
+                assert_eq!(o, e, "{:?} at {} bits differs for\n{}\nwith x = {}, y = {}",
+                    printer, bits, expr, x, y);
+            }
+            assert_eq!(out.lines().count(), expected.lines().count());
+        });
+    }
+}
+
+#[test]
+fn default_round_trip() {
+    for bits in WIDTHS {
+        UInt::with_width(bits, || {
+            let exprs = exprs(false);
+            let inputs = inputs();
+            // Shared subexpressions would be printed as variables.
+            let printed: Vec<_> = exprs[..EXPRS.len()].iter()
+                .map(|e| Expr::from_string(e.print_as_fn(Printer::Default, false)).unwrap())
+                .collect();
+            assert_eq!(expected(&printed, &inputs, false),
+                expected(&exprs[..EXPRS.len()], &inputs, false));
+        });
+    }
+}
+
+#[test]
+fn c() {
+    check(Printer::C, false, true);
+}
+
+#[test]
+fn c_signed() {
+    check(Printer::C, true, true);
+}
+
+#[test]
+fn rust() {
+    check(Printer::Rust, false, true);
+}
+
+#[test]
+fn rust_signed() {
+    check(Printer::Rust, true, true);
+}
+
+#[test]
+fn python() {
+    check(Printer::Python, false, true);
+}
+
+#[test]
+fn python_signed() {
+    check(Printer::Python, true, true);
+}
+
+#[test]
+fn javascript() {
+    check(Printer::JavaScript, false, true);
+}
+
+#[test]
+fn javascript_signed() {
+    check(Printer::JavaScript, true, true);
+}
+
+#[test]
+fn llvm() {
+    check(Printer::LlvmIr, false, true);
+}
+
+#[test]
+fn llvm_signed() {
+    check(Printer::LlvmIr, true, true);
+}
+
+#[test]
+fn llvm_block() {
+    // The inputs have the names of the results in older versions.
+    let src = "a = res0 * res1 + 1; return a / res1, a << res0, a";
+    for bits in WIDTHS {
+        UInt::with_width(bits, || {
+            let block = Block::<UInt>::from_string(src).unwrap();
+            let inputs = inputs();
+            let ty = format!("i{}", bits);
+            let ret_ty = format!("{{ {ty}, {ty}, {ty} }}");
+
+            let mut s = block.print_as_fn(Printer::LlvmIr, false);
+            s += "\ndeclare i32 @printf(i8*, ...)\n";
+            s += "@fmt = private constant [6 x i8] c\"%lld\\0A\\00\"\n";
+            s += "define i32 @main() {\n";
+            let mut expected = String::new();
+            for (n, &(x, y)) in inputs.iter().enumerate() {
+                writeln!(&mut s, "\t%r{n} = call {ret_ty} @f({ty} {x}, {ty} {y})").unwrap();
+                for i in 0..3 {
+                    write!(&mut s, "\t%v{n}.{i} = extractvalue {ret_ty} %r{n}, {i}\n\
+                        \t%e{n}.{i} = zext {ty} %v{n}.{i} to i64\n\
+                        \tcall i32 (i8*, ...) @printf(i8* getelementptr inbounds \
+                        ([6 x i8], [6 x i8]* @fmt, i64 0, i64 0), i64 %e{n}.{i})\n").unwrap();
+                }
+
+                let mut v = Valuation::zero(vec!["res0".to_owned(), "res1".to_owned()]);
+                v["res0"] = x;
+                v["res1"] = y;
+                for r in block.eval(&v, false) {
+                    writeln!(&mut expected, "{}", r).unwrap();
+                }
+            }
+            s += "\tret i32 0\n}\n";
+
+            let name = format!("block_{}", bits);
+            let Some(out) = run(&s, Printer::LlvmIr, &name) else {
+                eprintln!("Skipping {:?}, because the tool isn't installed.", Printer::LlvmIr);
+                return;
+            };
+            assert_eq!(out, expected, "{}", s);
+        });
+    }
+}
+
+#[test]
+fn block_signed() {
+    let src = "a = x * y - 1; return a / y, a >> x, a % (y | 1)";
+    for printer in [Printer::Python, Printer::JavaScript] {
+        for bits in WIDTHS {
+            UInt::with_width(bits, || {
+                let block = Block::<UInt>::from_string(src).unwrap();
+                let mut s = block.print_as_fn(printer, true);
+                s += "\n";
+                let mut expected = String::new();
+                for &(x, y) in &inputs() {
+                    let call = format!("f({}, {})",
+                        arg(x, printer, true), arg(y, printer, true));
+                    match printer {
+                        Printer::Python => writeln!(&mut s, "print(*{}, sep='\\n')", call),
+                        _ => writeln!(&mut s, "console.log({}.join('\\n'));", call),
+                    }.unwrap();
+
+                    let mut v = Valuation::zero(vec!["x".to_owned(), "y".to_owned()]);
+                    v["x"] = x;
+                    v["y"] = y;
+                    for r in block.eval(&v, true) {
+                        writeln!(&mut expected, "{}", r.to_i128()).unwrap();
+                    }
+                }
+
+                let name = format!("block_{:?}_{}", printer, bits).to_lowercase();
+                let Some(out) = run(&s, printer, &name) else {
+                    eprintln!("Skipping {:?}, because the tool isn't installed.", printer);
+                    return;
+                };
+                assert_eq!(out, expected, "{}", s);
+            });
+        }
+    }
+}
+
+#[test]
+fn smt_names() {
+    // The inputs have the names of the binders and functions in older versions.
+    let mut e = Expr::<UInt>::from_string("var0 * var0 + original").unwrap();
+    let mut shared = Rc::new(Expr::from_string("var0 + var1").unwrap());
+    e.substitute(&mut shared, "var0");
+    let inputs = e.vars();
+    let q = mba_wasm::verify::smt_query(&e, &e, false);
+
+    // The names bound by `let` and `define-fun`.
+    let defined: Vec<_> = q.match_indices("(let ((").map(|(i, m)| i + m.len())
+        .chain(q.match_indices("(define-fun ").map(|(i, m)| i + m.len()))
+        .map(|i| q[i..].split(' ').next().unwrap())
+        .collect();
+    assert_eq!(defined.len(), 4, "{}", q);
+    for d in defined {
+        assert!(!inputs.iter().any(|i| i == d), "{} is an input:\n{}", d, q);
+    }
+}
+
+#[test]
+fn signed_coefficients() {
+    UInt::with_width(8, || {
+        let c = |i: i128| Rc::new(Expr::Const(UInt::from_i128(i)));
+        let v = |n: &str| Rc::new(Expr::Var(n.to_owned()));
+        let x_and_y = Rc::new(Expr::And(v("x"), v("y")));
+        let y_plus_z = || Rc::new(Expr::Add(v("y"), v("z")));
+        let e = Expr::Add(
+            Rc::new(Expr::Add(
+                Rc::new(Expr::Mul(c(-1), v("x"))),
+                Rc::new(Expr::Mul(c(-1), y_plus_z())),
+            )),
+            Rc::new(Expr::Mul(c(-3), x_and_y)),
+        );
+        assert_eq!(e.print_as_fn(Printer::Default, true), "-x - (y + z) - 3 * (x & y)");
+
+        let e = Expr::Sub(v("x"), Rc::new(Expr::Mul(c(-1), y_plus_z())));
+        assert_eq!(e.print_as_fn(Printer::Default, true), "x + y + z");
+
+        // The negated minimum wouldn't fit.
+        let e = Expr::Add(v("x"), Rc::new(Expr::Mul(c(-128), v("y"))));
+        assert_eq!(e.print_as_fn(Printer::Default, true), "x + -128 * y");
+    });
+}
+
+/// The TeX output can't be run, so it is compared to a snapshot.
+#[test]
+fn tex() {
+    UInt::with_width(8, || {
+        let e = Expr::<UInt>::from_string("~x * -y + x / (y - 1) - x % y ^ (x & y | x << 1) >> 3").unwrap();
+        assert_eq!(e.print_as_fn(Printer::Tex, false),
+            "(\\overline{x} \\cdot -y + \\left\\lfloor\\frac{x}{y - 1}\\right\\rfloor - x \\bmod y) \
+            \\oplus (((x \\land y) \\lor (x \\ll 1)) \\gg 3)");
+        assert_eq!(e.print_as_fn(Printer::Tex, true),
+            "(\\overline{x} \\cdot -y + \\operatorname{trunc}\\left(\\frac{x}{y - 1}\\right) - x \\operatorname{rem} y) \
+            \\oplus (((x \\land y) \\lor (x \\ll 1)) \\gg 3)");
+
+        let block = Block::<UInt>::from_string("a = x * y; return a + ~a, a >> x").unwrap();
+        assert_eq!(block.print_as_fn(Printer::Tex, false), "\\begin{aligned}\n\
+            \\text{var}_{0} &= x \\cdot y \\\\\n\
+            f_{1}(x, y) &= \\text{var}_{0} + \\overline{\\text{var}_{0}} \\\\\n\
+            f_{2}(x, y) &= \\text{var}_{0} \\gg x\n\
+            \\end{aligned}");
+    });
+}
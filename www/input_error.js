@@ -0,0 +1,52 @@
+import { InputError } from './wasm.js'
+
+// Marks the input as invalid and shows the error below it.
+// If the error has a location, the part of the input
+// that caused the error is underlined.
+export function show_input_error(input, feedback, err) {
+    input.classList.add('is-invalid')
+    feedback.replaceChildren()
+
+    if (typeof err === 'string') {
+        feedback.textContent = err
+        return
+    }
+
+    if (!(err instanceof InputError)) {
+        console.log(err)
+        feedback.textContent = 'Unknown error. Check console.'
+        return
+    }
+
+    feedback.appendChild(document.createTextNode(err.message))
+
+    const start = err.start
+    const end = err.end
+    if (start === undefined || end === undefined) {
+        return
+    }
+
+    // Repeat the input with the error underlined.
+    const s = input.value
+    const code = document.createElement('code')
+    code.style.display = 'block'
+    code.style.whiteSpace = 'pre'
+    code.appendChild(document.createTextNode(s.substring(0, start)))
+    const marked = document.createElement('span')
+    marked.style.textDecoration = 'underline wavy red'
+    // If the error is at the end of the input, underline a space.
+    marked.textContent = end > start ? s.substring(start, end) : ' '
+    code.appendChild(marked)
+    code.appendChild(document.createTextNode(s.substring(end)))
+    feedback.appendChild(code)
+
+    // Also select the location in the input box.
+    input.focus()
+    input.setSelectionRange(start, end)
+}
+
+// Removes the error indication from the input.
+export function clear_input_error(input, feedback) {
+    input.classList.remove('is-invalid')
+    feedback.replaceChildren()
+}
@@ -1,5 +1,5 @@
 import './mathjax.js'
-import { solve_congruences, Width } from './wasm.js';
+import { solve_congruences } from './wasm.js';
 
 // Global tex render options.
 let tex_options = { scale: 1.2 }
@@ -15,6 +15,12 @@ const math = document.getElementById('math')
 // Changing the matrix text field updates the tex'd system.
 const show_eqs = () => {
     MathJax.reset()
+
+    // Powers of two are written as 2^k.
+    let modulus = mod.value.trim()
+    if (modulus.startsWith('2^'))
+        modulus = `2^{${modulus.slice(2).trim()}}`
+
     let eqs = '\\begin{align}'
     for (let line of mat.value.split('\n')) {
         line = line.trim()
@@ -32,7 +38,7 @@ const show_eqs = () => {
         }
 
         eqs = eqs.slice(0, -1)
-        eqs += `\\equiv ${rhs}&\\pmod{2^{${mod.options[mod.selectedIndex].label}}}\\\\`
+        eqs += `\\equiv ${rhs}&\\pmod{${modulus}}\\\\`
     }
     eqs += '\\end{align}'
 
@@ -60,7 +66,7 @@ b.onclick = () => {
             math.appendChild(MathJax.tex2chtml(str, tex_options))
         }
 
-        let s = solve_congruences(mat.value, Width[mod.value])
+        let s = solve_congruences(mat.value, mod.value)
 
         MathJax.reset()
         math.replaceChildren()
@@ -81,6 +87,11 @@ b.onclick = () => {
 
             add_text('The final solution to the original system is')
             add_math(s.final_solution)
+
+            if (s.solution_count !== '') {
+                add_text('The number of solutions is')
+                add_math(s.solution_count)
+            }
         }
 
         MathJax.set_css('mjx-solution-styles')
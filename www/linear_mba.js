@@ -1,4 +1,5 @@
-import { obfuscate_linear, normalize_op, ObfLinReq, Width, Printer } from './wasm.js';
+import { obfuscate_linear, normalize_op, ObfLinReq, Width, Printer, SolutionChoice } from './wasm.js';
+import { show_input_error, clear_input_error } from './input_error.js'
 import './mathjax.js'
 
 const btn = document.getElementById('obfuscate-btn')
@@ -9,7 +10,10 @@ const ops = document.getElementById('ops')
 const op_input = document.getElementById('op-input')
 const op_add = document.getElementById('op-add')
 const op_add_item = document.getElementById('op-add-item')
-const randomize = document.getElementById('random')
+const op_error = document.getElementById('op-error')
+const solution = document.getElementById('solution')
+const signed = document.getElementById('signed')
+const seed = document.getElementById('seed')
 const output_type = document.getElementById('output-type')
 const output_types = document.getElementsByName('output-type')
 
@@ -55,20 +59,22 @@ Only linear combinations of boolean operations are allowed.
 `
 })
 
-// Popover for the 'random solution' button.
-new bootstrap.Popover(document.getElementById('randomize-div'), {
+// Popover for the solution selection.
+new bootstrap.Popover(document.getElementById('solution-div'), {
     ...popover_config,
-    title: `Generate a random output`,
+    title: `How the solution is picked`,
     content:
 `
 Rewriting the input using the operations involves solving a 'System of Linear Congruences',
 which is very similar to 'Systems of Linear Equations' that are known from Linear Algebra.
 In the same way the solution also is a particular solution plus any vector in the kernel. 
-If randomize solution is disabled, the particular solution that the algorithm returns is used.
+'Random' adds a random vector in the kernel to the particular solution.
+'Particular' uses the particular solution that the algorithm returns.
 Since the algorithm is deterministic it will always be the same.
 Note that changing the order of the rewrite operations can change the solution.
-To get a canonical solution, we could define some sort of norm and try to find the smallest
-solution according to that norm, but this is future work.
+'Short coefficients' and 'Few operations' search for a solution whose coefficients
+are small as signed integers or that uses few of the operations,
+which makes the result easier to read.
 `
 })
 
@@ -115,6 +121,7 @@ for (const li of output_types) {
         }
         e.target.classList.add('active')
         output_type.textContent = e.target.textContent
+        output_type.dataset.printer = e.target.dataset.printer
     }
 }
 
@@ -126,18 +133,19 @@ const remove_op = (e) => {
 // Add an operation to the list of operations used for rewriting.
 const add_op = () => {
     // Normalize the operation and make sure it is valid.
-    const bits = Width[document.querySelector('input[name=bitness]:checked').value]
-    const s = normalize_op(op_input.value, bits)
-    
-    // If it isn't, indicate that.
-    if (s === '') {
-        op_input.classList.add('is-invalid')
+    const bits = new Width(Number(document.querySelector('input[name=bitness]:checked').value))
+    let s
+    try {
+        s = normalize_op(op_input.value, bits)
+    } catch (err) {
+        // If it isn't, indicate that.
+        show_input_error(op_input, op_error, err)
         op_input.parentElement.classList.add('is-invalid')
         return
     }
 
     // Remove potential prior indication.
-    op_input.classList.remove('is-invalid')
+    clear_input_error(op_input, op_error)
     op_input.parentElement.classList.remove('is-invalid')
 
     // Create a new list item.
@@ -171,9 +179,11 @@ op_input.onkeydown = (e) => {
 btn.onclick = () => {
     let req = new ObfLinReq()
     req.expr = input.value
-    req.randomize = randomize.checked
+    req.solution = SolutionChoice[solution.value]
+    req.signed = signed.checked
+    req.seed = seed.value === '' ? undefined : BigInt(seed.value)
 
-    const printer = Printer[output_type.innerText.trim()]
+    const printer = Printer[output_type.dataset.printer]
     req.printer = printer
 
     // Collect the rewrite ops.
@@ -182,14 +192,13 @@ btn.onclick = () => {
     }
 
     // Get the number of bits we are obfuscating for.
-    const bits = Width[document.querySelector('input[name=bitness]:checked').value]
-    req.bits = bits
+    const bits = Number(document.querySelector('input[name=bitness]:checked').value)
+    req.bits = new Width(bits)
 
     try {
         // Do the rewriting.
         const s = obfuscate_linear(req)
-        input.classList.remove('is-invalid')
-        input_error.textContent = ''
+        clear_input_error(input, input_error)
 
         // Display the result.
         output.replaceChildren()
@@ -201,7 +210,7 @@ btn.onclick = () => {
 
             // Very hacky and requires the code to contain commas only for the arguments.
             const args = s.split(',').map(() => '0').join(', ')
-            const ce_code = encodeURIComponent(`#include <cstdint>\n#include <iostream>\n\n${s}\n\nint main() {\n\tstd::cout << ${bits == Width.U8 ? '(uint32_t)' : ''}f(${args}) << "\\n";\n}`)
+            const ce_code = encodeURIComponent(`#include <cstdint>\n#include <iostream>\n\n${s}\n\nint main() {\n\tstd::cout << ${bits == 8 ? (signed.checked ? '(int32_t)' : '(uint32_t)') : ''}f(${args}) << "\\n";\n}`)
             const ce_btn = document.createElement('button')
             ce_btn.textContent = 'Open in Compiler Explorer'
             ce_btn.classList.add('btn', 'btn-secondary')
@@ -231,16 +240,12 @@ btn.onclick = () => {
             output.appendChild(MathJax.tex2chtml(s, { scale: 1.3 }))
             MathJax.set_css('mathjax-styles')
         } else {
-            output.textContent = s
+            const code = document.createElement('pre')
+            code.textContent = s
+            output.appendChild(code)
         }
     } catch (err) {
-        input.classList.add('is-invalid')
         output.textContent = ''
-        if (typeof err === 'string') {
-            input_error.textContent = err
-        } else {
-            console.log(err)
-            input_error.textContent = 'Unknown error. Check console.'
-        }
+        show_input_error(input, input_error, err)
     }
 }
\ No newline at end of file
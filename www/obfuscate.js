@@ -1,4 +1,6 @@
-import { obfuscate, ObfuscationConfig, Width, Printer } from './wasm.js';
+import { obfuscate, ObfuscationConfig, Width, Printer, SolutionChoice } from './wasm.js';
+import { show_input_error, clear_input_error } from './input_error.js'
+import './mathjax.js'
 
 const btn = document.getElementById('obfuscate-btn')
 const input = document.getElementById('input')
@@ -9,6 +11,10 @@ const output_types = document.getElementsByName('output-type')
 const aux_vars = document.getElementById('aux-vars')
 const rewrite_ops = document.getElementById('rewrite-ops')
 const rewrite_depth = document.getElementById('rewrite-depth')
+const perm_poly = document.getElementById('perm-poly')
+const signed = document.getElementById('signed')
+const solution = document.getElementById('solution')
+const seed = document.getElementById('seed')
 
 // Highlights inline code.
 function hi_in(code) {
@@ -37,6 +43,29 @@ and leave the non-linear operations as is.
 `
 })
 
+// Popover for the permutation polynomial checkbox.
+new bootstrap.Popover(perm_poly.parentElement, {
+    ...popover_config,
+    title: 'Permutation polynomial',
+    content:
+`
+Wraps each linear MBA expression ${hi_in('e')} in ${hi_in('q(p(e))')},
+where ${hi_in('p')} is a random permutation polynomial and ${hi_in('q')} its inverse.
+This makes the result non-linear but also a lot longer.
+`
+})
+
+// Popover for the seed input.
+new bootstrap.Popover(seed, {
+    ...popover_config,
+    title: 'Seed',
+    content:
+`
+The same seed and settings always produce the same output.
+Leave this empty to use a random seed.
+`
+})
+
 // 'What is Mixed Boolean-Arithmetic?'
 document.getElementById('acc-col-1').children[0].innerHTML =
 `
@@ -57,27 +86,35 @@ for (const li of output_types) {
         }
         e.target.classList.add('active')
         output_type.textContent = e.target.textContent
+        output_type.dataset.printer = e.target.dataset.printer
     }
 }
 
 // Do the obfuscation.
 btn.onclick = () => {
-    const printer = Printer[output_type.innerText.trim()]
-    const width = Width[document.querySelector('input[name=bitness]:checked').value]
+    const printer = Printer[output_type.dataset.printer]
+    const bits = Number(document.querySelector('input[name=bitness]:checked').value)
 
     let cfg = new ObfuscationConfig()
     cfg.expr = input.value
     cfg.printer = printer
-    cfg.width = width
+    cfg.width = new Width(bits)
     cfg.aux_vars = Number(aux_vars.value)
     cfg.rewrite_count = Number(rewrite_ops.value)
     cfg.rewrite_depth = Number(rewrite_depth.value)
+    cfg.perm_poly = perm_poly.checked
+    cfg.solution = SolutionChoice[solution.value]
+    cfg.signed = signed.checked
+    cfg.seed = seed.value === '' ? undefined : BigInt(seed.value)
 
     try {
         // Do the rewriting.
-        const s = postprocess_code(obfuscate(cfg))
-        input.classList.remove('is-invalid')
-        input_error.textContent = ''
+        // Python doesn't allow breaking lines anywhere
+        // and MathJax does its own line breaking.
+        const code = obfuscate(cfg)
+        const s = printer == Printer.Python || printer == Printer.Tex
+            ? code : postprocess_code(code)
+        clear_input_error(input, input_error)
 
         // Display the result.
         output.replaceChildren()
@@ -87,9 +124,16 @@ btn.onclick = () => {
             code.innerHTML = Prism.highlight(s, Prism.languages.c, 'c')
             output.appendChild(code)
 
-            // Very hacky and requires the code to contain commas only for the arguments.
-            const args = s.split(',').map(() => '0').join(', ')
-            const ce_code = encodeURIComponent(`#include <cstdint>\n#include <iostream>\n\n${s}\n\nint main() {\n\tstd::cout << ${width == Width.U8 ? '(uint32_t)' : ''}f(${args}) << "\\n";\n}`)
+            const args = fn_args(s).map(() => '0').join(', ')
+            const cast = bits == 8 ? (cfg.signed ? '(int32_t)' : '(uint32_t)') : ''
+
+            // Blocks with several results return a struct.
+            const fields = !s.startsWith('struct f_result') ? []
+                : [...s.substring(0, s.indexOf('};')).matchAll(/ (r\d+);/g)].map(m => m[1])
+            const print = fields.length == 0 ? `${cast}f(${args})`
+                : `${fields.map(r => `${cast}res.${r}`).join(' << " " << ')}`
+            const res = fields.length == 0 ? '' : `\tauto res = f(${args});\n`
+            const ce_code = encodeURIComponent(`#include <cstdint>\n#include <iostream>\n\n${s}\n\nint main() {\n${res}\tstd::cout << ${print} << "\\n";\n}`)
             const ce_btn = document.createElement('button')
             ce_btn.textContent = 'Open in Compiler Explorer'
             ce_btn.classList.add('btn', 'btn-secondary')
@@ -107,29 +151,34 @@ btn.onclick = () => {
             pg_btn.textContent = 'Open in Rust Playground'
             pg_btn.classList.add('btn', 'btn-secondary')
 
-            // Very hacky and requires the code to contain commas only for the arguments.
-            const args = s.split(',').map(() => 'Wrapping(0)').join(', ')
-            const pg_code = encodeURIComponent(`use std::num::Wrapping;\n\nfn main() {\n\tprintln!("{}", f(${args}));\n}\n\n${s}`)
+            // Debug also prints the tuples returned by blocks with several results.
+            const args = fn_args(s).map(() => 'Wrapping(0)').join(', ')
+            const pg_code = encodeURIComponent(`use std::num::Wrapping;\n\nfn main() {\n\tprintln!("{:?}", f(${args}));\n}\n\n${s}`)
             pg_btn.onclick = () => {
                 window.open(`https://play.rust-lang.org/?version=stable&mode=release&edition=2021&code=${pg_code}`)
             }
             output.appendChild(pg_btn)
-        }
-        else {
-            output.textContent = s
+        } else if (printer == Printer.Tex) {
+            MathJax.reset()
+            output.appendChild(MathJax.tex2chtml(s, { scale: 1.3 }))
+            MathJax.set_css('mathjax-styles')
+        } else {
+            const code = document.createElement('pre')
+            code.textContent = s
+            output.appendChild(code)
         }
     } catch (err) {
-        input.classList.add('is-invalid')
         output.textContent = ''
-        if (typeof err === 'string') {
-            input_error.textContent = err
-        } else {
-            console.log(err)
-            input_error.textContent = 'Unknown error. Check console.'
-        }
+        show_input_error(input, input_error, err)
     }
 }
 
+// Returns the arguments in the signature of the function f.
+function fn_args(code) {
+    const start = code.indexOf(' f(') + 3
+    return code.substring(start, code.indexOf(')', start)).split(',')
+}
+
 // Hide this ugly code down here.
 function postprocess_code(code) {
     let s = ''
@@ -5,6 +5,7 @@ const input = document.getElementById('input')
 const input_error = document.getElementById('input-error')
 const invert_btn = document.getElementById('invert-btn')
 const rand = document.getElementById('rand-poly')
+const seed = document.getElementById('seed')
 const algorithm = document.getElementById('algorithm')
 const algorithms = document.getElementsByName('algorithm')
 const output = document.getElementById('output')
@@ -21,14 +22,14 @@ for (const li of algorithms) {
 }
 
 rand.onclick = () => {
-    const bits = Width[document.querySelector('input[name=width]:checked').value]
-    const p = rand_poly(bits)
+    const bits = new Width(Number(document.querySelector('input[name=width]:checked').value))
+    const p = rand_poly(bits, seed.value === '' ? undefined : BigInt(seed.value))
     input.value = p
 }
 
 invert_btn.onclick = () => {
     const poly = input.value
-    const bits = Width[document.querySelector('input[name=width]:checked').value]
+    const bits = new Width(Number(document.querySelector('input[name=width]:checked').value))
     const alg = algorithm.innerText.trim()
     try {
         input.classList.remove('is-invalid')